@@ -6,6 +6,7 @@ pub use self::animation_frame::*;
 pub use self::async_wrapper::*;
 pub use self::canvas::*;
 pub use self::closure::*;
+#[cfg(target_arch = "wasm32")]
 pub use self::elements::*;
 pub use self::json::*;
 pub use self::utils::*;
@@ -30,23 +31,55 @@ pub mod window {
 pub mod utils {
     use super::*;
 
+    #[cfg(target_arch = "wasm32")]
     macro_rules! log {
         ($($t:tt)*) => {
             web_sys::console::log_1(&format!($($t)*).into());
         };
     }
 
+    // ブラウザ以外（ネイティブのテスト）では標準エラー出力に出す
+    #[cfg(not(target_arch = "wasm32"))]
+    macro_rules! log {
+        ($($t:tt)*) => {
+            eprintln!($($t)*);
+        };
+    }
+
     pub fn now() -> Result<f64> {
         Ok(window()?
             .performance()
             .ok_or(anyhow!("No performance object found on window"))?
             .now())
     }
+
+    // ウィンドウの表示領域の大きさ（CSS ピクセル）
+    pub fn window_size() -> Result<(f64, f64)> {
+        let window = window()?;
+        let width = window
+            .inner_width()
+            .map_err(|js_value| anyhow!("Error getting window width {:#?}", js_value))?
+            .as_f64()
+            .ok_or(anyhow!("window width should be a number"))?;
+        let height = window
+            .inner_height()
+            .map_err(|js_value| anyhow!("Error getting window height {:#?}", js_value))?
+            .as_f64()
+            .ok_or(anyhow!("window height should be a number"))?;
+        Ok((width, height))
+    }
+
+    // CSS ピクセル 1 つあたりの、画面の物理的なピクセル数
+    pub fn device_pixel_ratio() -> Result<f64> {
+        Ok(window()?.device_pixel_ratio())
+    }
 }
 
 pub mod canvas {
     use super::*;
-    use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+    #[cfg(target_arch = "wasm32")]
+    use web_sys::CanvasRenderingContext2d;
+    use web_sys::HtmlCanvasElement;
 
     pub fn canvas() -> Result<HtmlCanvasElement> {
         document()?
@@ -56,8 +89,44 @@ pub mod canvas {
             .map_err(|element| anyhow!("Error converting {:#?} to HtmlCanvasElement", element))
     }
 
+    // canvas を display（CSS ピクセル）の大きさで表示し、backing のピクセル数を持たせる
+    // canvas の大きさを変えると描画した内容と context の状態は消える
+    pub fn resize_canvas(display: (f64, f64), backing: (u32, u32)) -> Result<()> {
+        let canvas = canvas()?;
+        let style = canvas.style();
+        style
+            .set_property("width", &format!("{}px", display.0))
+            .map_err(|js_value| anyhow!("Error setting canvas width {:#?}", js_value))?;
+        style
+            .set_property("height", &format!("{}px", display.1))
+            .map_err(|js_value| anyhow!("Error setting canvas height {:#?}", js_value))?;
+        canvas.set_width(backing.0);
+        canvas.set_height(backing.1);
+        Ok(())
+    }
+
+    #[cfg(target_arch = "wasm32")]
     pub fn context() -> Result<CanvasRenderingContext2d> {
-        canvas()?
+        context_of(&canvas()?)
+    }
+
+    // 画面には表示しない width x height の canvas を作成し、その 2d context を返す
+    #[cfg(all(target_arch = "wasm32", feature = "pixel_collision"))]
+    pub fn offscreen_context(width: u32, height: u32) -> Result<CanvasRenderingContext2d> {
+        let canvas = document()?
+            .create_element("canvas")
+            .map_err(|js_value| anyhow!("Error creating canvas {:#?}", js_value))?
+            .dyn_into::<HtmlCanvasElement>()
+            .map_err(|element| anyhow!("Error converting {:#?} to HtmlCanvasElement", element))?;
+        canvas.set_width(width);
+        canvas.set_height(height);
+
+        context_of(&canvas)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn context_of(canvas: &HtmlCanvasElement) -> Result<CanvasRenderingContext2d> {
+        canvas
             .get_context("2d")
             .map_err(|js_value| anyhow!("Error getting 2d context {:#?} on canvas", js_value))?
             .ok_or(anyhow!("should have a 2d context on canvas"))?
@@ -115,6 +184,7 @@ pub mod json {
     }
 }
 
+#[cfg(target_arch = "wasm32")]
 pub mod elements {
     use super::*;
     use web_sys::HtmlImageElement;
@@ -126,8 +196,11 @@ pub mod elements {
 }
 
 pub mod closure {
-    use wasm_bindgen::closure::{Closure, IntoWasmClosure, WasmClosureFnOnce};
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen::closure::WasmClosureFnOnce;
+    use wasm_bindgen::closure::{Closure, IntoWasmClosure};
 
+    #[cfg(target_arch = "wasm32")]
     pub fn closure_once<F, A, R>(fn_once: F) -> Closure<F::FnMut>
     where
         F: 'static + WasmClosureFnOnce<A, R>,
@@ -1,12 +1,16 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 
 use crate::browser::{self, LoopClosure};
 
 use self::{
-    key_state::{prepare_input, process_input, KeyState},
+    key_state::{prepare_input, process_input, release_input, KeyState},
     renderer::Renderer,
+    viewport::Viewport,
 };
 
 #[async_trait(?Send)]
@@ -14,6 +18,12 @@ pub trait Game {
     async fn initialize(&self) -> Result<Box<dyn Game>>;
     fn update(&mut self, keystate: &KeyState);
     fn draw(&self, renderer: &Renderer);
+    // ゲームの画面の大きさと、それをウィンドウに合わせる方法
+    fn viewport(&self) -> Viewport;
+    // ゲームの進行速度の倍率（1 より小さいとスローモーションになる）
+    fn time_scale(&self) -> f32 {
+        1.
+    }
 }
 
 const FRAME_SIZE: f32 = 1. / 60. * 1000.;
@@ -27,8 +37,21 @@ pub struct GameLoop {
 
 type SharedLoopClosure = Rc<RefCell<Option<LoopClosure>>>;
 
+// start したゲームループを止めるためのハンドル（drop しても止まらない）
+pub struct GameLoopHandle {
+    running: Rc<Cell<bool>>,
+}
+
+impl GameLoopHandle {
+    // 次のフレームからは update も draw もせず、canvas の入力も受け取らない
+    pub fn stop(&self) -> Result<()> {
+        self.running.set(false);
+        release_input()
+    }
+}
+
 impl GameLoop {
-    pub async fn start(game: impl Game + 'static) -> Result<()> {
+    pub async fn start(game: impl Game + 'static) -> Result<GameLoopHandle> {
         // キー入力を受け取るチャンネルを作成
         let mut keyevent_receiver = prepare_input()?;
         // キー入力の状態を保持する構造体を作成
@@ -42,6 +65,7 @@ impl GameLoop {
         };
 
         let renderer = Renderer::new()?;
+        let mut viewport = game.viewport();
 
         // js における以下のコードを模したもの
         //   (なお requestAnimationFrameは渡した関数をブラウザの表示を邪魔しないタイミングで処理されるようにする関数)
@@ -57,19 +81,36 @@ impl GameLoop {
         //
         let f: SharedLoopClosure = Rc::new(RefCell::new(None));
         let g = f.clone();
+        let running = Rc::new(Cell::new(true));
+        let handle = GameLoopHandle {
+            running: running.clone(),
+        };
 
         *g.borrow_mut() = Some(browser::create_wasm_closure(move |perf: f64| {
+            // 止められた後は、次のフレームをリクエストせずに終える
+            if !running.get() {
+                return;
+            }
+
             // perf は、このコールバック関数が呼び出された時点の performance.now() の値（＝その時点の時刻）
             // この値を用いて、前回のフレームからの経過時間を計算し、それを累積時間に加算する
             game_loop.accumulated_delta += (perf - game_loop.last_frame) as f32;
 
-            // キー入力を処理する
-            process_input(&mut key_state, &mut keyevent_receiver);
+            // ウィンドウの大きさや devicePixelRatio が変わっていたら、canvas をそれに合わせる
+            // （ブラウザの拡大率を変えたり、解像度の違う画面にウィンドウを移したりしても変わるので、毎フレーム確かめる）
+            // 失敗してもゲームループは止めず、そのフレームは前の大きさのまま描画して次のフレームでやり直す
+            if let Err(err) = fit_canvas(&mut viewport, &renderer) {
+                log!("Error resizing canvas {:#?}", err);
+            }
+
+            // キー入力と、マウスやタッチの入力を処理する
+            process_input(&mut key_state, &mut keyevent_receiver, &viewport);
 
             // 累積時間分だけ update を繰り返す
-            while game_loop.accumulated_delta > FRAME_SIZE {
+            // time_scale が小さいときは 1 フレームあたりの時間を長くして、update の回数を減らす
+            while game_loop.accumulated_delta > FRAME_SIZE / game.time_scale() {
+                game_loop.accumulated_delta -= FRAME_SIZE / game.time_scale();
                 game.update(&key_state);
-                game_loop.accumulated_delta -= FRAME_SIZE;
             }
 
             game_loop.last_frame = perf;
@@ -81,78 +122,228 @@ impl GameLoop {
 
         browser::request_animation_frame(g.borrow().as_ref().unwrap())?;
 
-        Ok(())
+        Ok(handle)
     }
 }
 
+// ウィンドウの大きさか devicePixelRatio が前回から変わったときだけ、canvas の表示する大きさとピクセル数を合わせる
+// canvas と renderer の両方に反映できたときだけ viewport を更新する
+fn fit_canvas(viewport: &mut Viewport, renderer: &Renderer) -> Result<()> {
+    let (width, height) = browser::window_size()?;
+    let mut resized = viewport.clone();
+    if resized.resize(width, height, browser::device_pixel_ratio()?) {
+        browser::resize_canvas(resized.display_size(), resized.backing_size())?;
+        renderer.set_viewport(&resized)?;
+        *viewport = resized;
+    }
+    Ok(())
+}
+
 pub mod renderer {
-    use anyhow::{anyhow, Result};
-    use web_sys::{CanvasRenderingContext2d, HtmlImageElement};
+    use serde::Serialize;
+
+    // 描画する画像の要素と、それを描画する Renderer
+    #[cfg(target_arch = "wasm32")]
+    pub use self::canvas::Renderer;
+    #[cfg(not(target_arch = "wasm32"))]
+    pub use self::headless::{ImageElement, Renderer};
+    #[cfg(target_arch = "wasm32")]
+    pub use web_sys::HtmlImageElement as ImageElement;
+
+    // ブラウザの canvas に描画する Renderer
+    #[cfg(target_arch = "wasm32")]
+    mod canvas {
+        use anyhow::{anyhow, Result};
+        use std::cell::Cell;
+        use web_sys::CanvasRenderingContext2d;
 
-    use crate::browser;
+        use super::{ImageElement, Point, Rect};
+        use crate::browser;
+        use crate::engine::{camera::Camera, viewport::Viewport};
 
-    // HtmlRenderingContext2d のラッパー
-    pub struct Renderer {
-        context: CanvasRenderingContext2d,
-    }
+        const TEXT_FONT: &str = "24px sans-serif";
 
-    impl Renderer {
-        pub fn new() -> Result<Self> {
-            Ok(Self {
-                context: browser::context()?,
-            })
+        // HtmlRenderingContext2d のラッパー
+        pub struct Renderer {
+            context: CanvasRenderingContext2d,
+            // ゲームの画面の 1 ピクセルに対応する canvas のピクセル数
+            scale: Cell<(f64, f64)>,
         }
 
-        pub fn clear(&self, rect: &Rect) {
-            self.context.clear_rect(
-                rect.x().into(),
-                rect.y().into(),
-                rect.w.into(),
-                rect.h.into(),
-            );
+        impl Renderer {
+            pub fn new() -> Result<Self> {
+                Ok(Self {
+                    context: browser::context()?,
+                    scale: Cell::new((1., 1.)),
+                })
+            }
+
+            pub fn clear(&self, rect: &Rect) {
+                self.context.clear_rect(
+                    rect.x().into(),
+                    rect.y().into(),
+                    rect.w.into(),
+                    rect.h.into(),
+                );
+            }
+
+            pub fn draw_rect(&self, rect: &Rect) {
+                self.context.begin_path();
+                self.context.set_stroke_style(&"red".into());
+                self.context.rect(
+                    rect.x().into(),
+                    rect.y().into(),
+                    rect.w.into(),
+                    rect.h.into(),
+                );
+                self.context.stroke();
+            }
+
+            pub fn draw_text(&self, text: &str, position: &Point) {
+                self.context.set_font(TEXT_FONT);
+                self.context.set_fill_style(&"black".into());
+                // 文字列の描画に失敗してもゲームの進行には影響しないので、エラーは無視する
+                let _ = self
+                    .context
+                    .fill_text(text, position.x.into(), position.y.into());
+            }
+
+            pub fn draw_image(
+                &self,
+                image: &ImageElement,
+                frame: &Rect,
+                destination: &Rect,
+            ) -> Result<()> {
+                self.context
+                    .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                        image,
+                        frame.x() as f64,
+                        frame.y() as f64,
+                        frame.w as f64,
+                        frame.h as f64,
+                        destination.x() as f64,
+                        destination.y() as f64,
+                        destination.w as f64,
+                        destination.h as f64,
+                    )
+                    .map_err(|js_value| anyhow!("Error drawing image {:#?}", js_value))?;
+
+                Ok(())
+            }
+
+            pub fn draw_entire_image(&self, image: &ImageElement, position: &Point) -> Result<()> {
+                self.context
+                    .draw_image_with_html_image_element(image, position.x as f64, position.y as f64)
+                    .map_err(|js_value| anyhow!("Error drawing image {:#?}", js_value))?;
+
+                Ok(())
+            }
+
+            // 以降の描画を、camera に映るワールド座標で行う
+            pub fn set_camera(&self, camera: &Camera) -> Result<()> {
+                let origin = camera.origin();
+                self.translate(-origin.x, -origin.y)
+            }
+
+            // 以降の描画を、画面上の座標で行う（スコアなど画面に固定して表示するもの）
+            pub fn reset_camera(&self) -> Result<()> {
+                self.translate(0, 0)
+            }
+
+            // canvas の大きさを viewport に合わせた後に呼び出し、ゲームの画面の座標を canvas 全体に拡大して描画する
+            // （canvas の大きさを変えると変換行列が初期化されるので、ここで画面上の座標に戻す）
+            pub fn set_viewport(&self, viewport: &Viewport) -> Result<()> {
+                self.scale.set(viewport.scale());
+                self.reset_camera()
+            }
+
+            fn translate(&self, x: i16, y: i16) -> Result<()> {
+                let (scale_x, scale_y) = self.scale.get();
+                self.context
+                    .set_transform(
+                        scale_x,
+                        0.,
+                        0.,
+                        scale_y,
+                        x as f64 * scale_x,
+                        y as f64 * scale_y,
+                    )
+                    .map_err(|js_value| anyhow!("Error setting transform {:#?}", js_value))
+            }
         }
+    }
 
-        pub fn draw_rect(&self, rect: &Rect) {
-            self.context.begin_path();
-            self.context.set_stroke_style(&"red".into());
-            self.context.rect(
-                rect.x().into(),
-                rect.y().into(),
-                rect.w.into(),
-                rect.h.into(),
-            );
-            self.context.stroke();
-        }
-
-        pub fn draw_image(
-            &self,
-            image: &HtmlImageElement,
-            frame: &Rect,
-            destination: &Rect,
-        ) -> Result<()> {
-            self.context
-                .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-                    &image,
-                    frame.x() as f64,
-                    frame.y() as f64,
-                    frame.w as f64,
-                    frame.h as f64,
-                    destination.x() as f64,
-                    destination.y() as f64,
-                    destination.w as f64,
-                    destination.h as f64,
-                )
-                .map_err(|js_value| anyhow!("Error drawing image {:#?}", js_value))?;
+    // ブラウザ以外（ネイティブのテスト）で使う、何も描画しない Renderer と大きさだけを持つ画像
+    // ゲームのロジックをブラウザなしで動かせるように、canvas の Renderer と同じメソッドを持つ
+    #[cfg(not(target_arch = "wasm32"))]
+    mod headless {
+        use anyhow::Result;
+
+        use super::{Point, Rect};
+        use crate::engine::{camera::Camera, viewport::Viewport};
+
+        pub struct Renderer;
+
+        impl Renderer {
+            pub fn new() -> Result<Self> {
+                Ok(Self)
+            }
+
+            pub fn clear(&self, _rect: &Rect) {}
+
+            pub fn draw_rect(&self, _rect: &Rect) {}
+
+            pub fn draw_text(&self, _text: &str, _position: &Point) {}
+
+            pub fn draw_image(
+                &self,
+                _image: &ImageElement,
+                _frame: &Rect,
+                _destination: &Rect,
+            ) -> Result<()> {
+                Ok(())
+            }
+
+            pub fn draw_entire_image(
+                &self,
+                _image: &ImageElement,
+                _position: &Point,
+            ) -> Result<()> {
+                Ok(())
+            }
+
+            pub fn set_camera(&self, _camera: &Camera) -> Result<()> {
+                Ok(())
+            }
+
+            pub fn reset_camera(&self) -> Result<()> {
+                Ok(())
+            }
 
-            Ok(())
+            pub fn set_viewport(&self, _viewport: &Viewport) -> Result<()> {
+                Ok(())
+            }
         }
 
-        pub fn draw_entire_image(&self, image: &HtmlImageElement, position: &Point) -> Result<()> {
-            self.context
-                .draw_image_with_html_image_element(&image, position.x as f64, position.y as f64)
-                .map_err(|js_value| anyhow!("Error drawing image {:#?}", js_value))?;
+        // HtmlImageElement の代わりに使う画像
+        #[derive(Clone)]
+        pub struct ImageElement {
+            width: u32,
+            height: u32,
+        }
+
+        impl ImageElement {
+            pub fn new(width: u32, height: u32) -> Self {
+                Self { width, height }
+            }
+
+            pub fn width(&self) -> u32 {
+                self.width
+            }
 
-            Ok(())
+            pub fn height(&self) -> u32 {
+                self.height
+            }
         }
     }
 
@@ -207,12 +398,33 @@ pub mod renderer {
             self.y() + self.h
         }
 
+        // 2 つの矩形が重なっている部分
+        pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+            self.intersects(other).then(|| {
+                let x = self.x().max(other.x());
+                let y = self.y().max(other.y());
+                Rect::new_from_x_y(
+                    x,
+                    y,
+                    self.right().min(other.right()) - x,
+                    self.bottom().min(other.bottom()) - y,
+                )
+            })
+        }
+
+        pub fn center(&self) -> Point {
+            Point {
+                x: self.x() + self.w / 2,
+                y: self.y() + self.h / 2,
+            }
+        }
+
         pub fn move_by(&mut self, diff: Point) {
             self.position.move_by(diff)
         }
     }
 
-    #[derive(Clone, Copy)]
+    #[derive(Clone, Copy, Debug)]
     pub struct Point {
         pub x: i16,
         pub y: i16,
@@ -227,17 +439,158 @@ pub mod renderer {
         }
     }
 
+    // 物理演算で使う、1 ピクセル未満の精度を持つ位置や速度
+    // 描画や当たり判定に使うときは、ピクセル単位の Point に丸める
+    #[derive(Clone, Copy, Serialize)]
+    pub struct Vector {
+        pub x: f32,
+        pub y: f32,
+    }
+
+    impl Vector {
+        pub fn move_by(&mut self, rhs: Point) {
+            self.x += rhs.x as f32;
+            self.y += rhs.y as f32;
+        }
+    }
+
+    impl From<Point> for Vector {
+        fn from(point: Point) -> Self {
+            Vector {
+                x: point.x as f32,
+                y: point.y as f32,
+            }
+        }
+    }
+
+    // 画像の透明度から作る、ピクセル単位の当たり判定用のマスク
+    // ピクセルを読み取るには canvas が必要なので、ブラウザ以外ではマスクを作らない（AABB だけで判定する）
+    #[cfg(feature = "pixel_collision")]
+    pub mod mask {
+        #[cfg(target_arch = "wasm32")]
+        use anyhow::{anyhow, Result};
+        use std::rc::Rc;
+        #[cfg(target_arch = "wasm32")]
+        use std::{cell::RefCell, collections::HashMap};
+
+        use super::{ImageElement, Point, Rect};
+        #[cfg(target_arch = "wasm32")]
+        use crate::browser;
+
+        // この値以上の alpha を持つピクセルを不透明とみなす
+        #[cfg(target_arch = "wasm32")]
+        const ALPHA_THRESHOLD: u8 = 128;
+
+        // 画像の src と切り出す範囲ごとに、作成済みのマスクを保持する
+        // （マスクを作れなかった場合も None を保持して、毎フレーム作り直さないようにする）
+        #[cfg(target_arch = "wasm32")]
+        type MaskKey = (String, i16, i16, i16, i16);
+        #[cfg(target_arch = "wasm32")]
+        thread_local! {
+            static MASKS: RefCell<HashMap<MaskKey, Option<Rc<AlphaMask>>>> =
+                RefCell::new(HashMap::new());
+        }
+
+        pub struct AlphaMask {
+            width: i16,
+            height: i16,
+            opaque: Vec<bool>,
+        }
+
+        impl AlphaMask {
+            // image の source の範囲のマスクを返す（一度作ったマスクは使い回す）
+            #[cfg(target_arch = "wasm32")]
+            pub fn cached(image: &ImageElement, source: &Rect) -> Option<Rc<AlphaMask>> {
+                let key = (image.src(), source.x(), source.y(), source.w, source.h);
+                MASKS.with(|masks| {
+                    masks
+                        .borrow_mut()
+                        .entry(key)
+                        .or_insert_with(|| AlphaMask::from_image(image, source).ok().map(Rc::new))
+                        .clone()
+                })
+            }
+
+            #[cfg(not(target_arch = "wasm32"))]
+            pub fn cached(_image: &ImageElement, _source: &Rect) -> Option<Rc<AlphaMask>> {
+                None
+            }
+
+            // 画面に表示しない canvas に画像を描画し、getImageData で各ピクセルの alpha を読み取る
+            #[cfg(target_arch = "wasm32")]
+            fn from_image(image: &ImageElement, source: &Rect) -> Result<Self> {
+                let context = browser::offscreen_context(source.w as u32, source.h as u32)?;
+                context
+                    .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                        image,
+                        source.x() as f64,
+                        source.y() as f64,
+                        source.w as f64,
+                        source.h as f64,
+                        0.,
+                        0.,
+                        source.w as f64,
+                        source.h as f64,
+                    )
+                    .map_err(|js_value| anyhow!("Error drawing image {:#?}", js_value))?;
+                let image_data = context
+                    .get_image_data(0., 0., source.w as f64, source.h as f64)
+                    .map_err(|js_value| anyhow!("Error getting image data {:#?}", js_value))?;
+
+                // data は 1 ピクセルあたり RGBA の 4 バイト
+                let opaque = image_data
+                    .data()
+                    .chunks_exact(4)
+                    .map(|rgba| rgba[3] >= ALPHA_THRESHOLD)
+                    .collect();
+
+                Ok(Self {
+                    width: source.w,
+                    height: source.h,
+                    opaque,
+                })
+            }
+
+            // マスクの左上を原点とした (x, y) のピクセルが不透明かどうか
+            fn is_opaque(&self, x: i16, y: i16) -> bool {
+                (0..self.width).contains(&x)
+                    && (0..self.height).contains(&y)
+                    && self.opaque[(y as usize) * (self.width as usize) + x as usize]
+            }
+        }
+
+        // a を a_origin に、b を b_origin に置いたとき、region の中に両方が不透明なピクセルがあるかどうか
+        pub fn overlaps(
+            a: &AlphaMask,
+            a_origin: Point,
+            b: &AlphaMask,
+            b_origin: Point,
+            region: &Rect,
+        ) -> bool {
+            (region.y()..region.bottom()).any(|y| {
+                (region.x()..region.right()).any(|x| {
+                    a.is_opaque(x - a_origin.x, y - a_origin.y)
+                        && b.is_opaque(x - b_origin.x, y - b_origin.y)
+                })
+            })
+        }
+    }
+
     pub mod image {
         use anyhow::{anyhow, Result};
+        #[cfg(target_arch = "wasm32")]
         use futures::channel::oneshot;
+        #[cfg(target_arch = "wasm32")]
         use std::{rc::Rc, sync::Mutex};
+        #[cfg(target_arch = "wasm32")]
         use wasm_bindgen::{JsCast, JsValue};
-        use web_sys::HtmlImageElement;
 
-        use super::{Point, Renderer};
+        use super::{ImageElement, Point, Renderer};
+        #[cfg(target_arch = "wasm32")]
         use crate::browser;
 
-        pub async fn load_image(source: &str) -> Result<HtmlImageElement> {
+        #[cfg(target_arch = "wasm32")]
+        pub async fn load_image(source: &str) -> Result<ImageElement> {
             // ImageHtmlElement の作成
             let image = browser::new_image()?;
 
@@ -273,14 +626,20 @@ pub mod renderer {
             Ok(image)
         }
 
+        // ブラウザ以外では画像を読み込めない（テストでは ImageElement::new で大きさを指定して作る）
+        #[cfg(not(target_arch = "wasm32"))]
+        pub async fn load_image(source: &str) -> Result<ImageElement> {
+            Err(anyhow!("Cannot load {} outside the browser", source))
+        }
+
         // Renderer 上の画像を表す構造体
         pub struct Image {
-            element: HtmlImageElement,
+            element: ImageElement,
             position: Point,
         }
 
         impl Image {
-            pub fn new(element: HtmlImageElement, position: Point) -> Self {
+            pub fn new(element: ImageElement, position: Point) -> Self {
                 Self { element, position }
             }
 
@@ -305,12 +664,21 @@ pub mod renderer {
                 self.position.x += velocity;
             }
 
+            pub fn move_by(&mut self, diff: Point) {
+                self.position.move_by(diff);
+            }
+
             pub fn set_x(&mut self, x: i16) {
                 self.position.x = x;
             }
 
-            pub(crate) fn right(&self) -> i16 {
-                self.position.x + self.width()
+            // 画像全体のマスク（左上は position に置かれる）
+            #[cfg(feature = "pixel_collision")]
+            pub fn mask(&self) -> Option<std::rc::Rc<super::mask::AlphaMask>> {
+                super::mask::AlphaMask::cached(
+                    &self.element,
+                    &super::Rect::new_from_x_y(0, 0, self.width(), self.height()),
+                )
             }
         }
     }
@@ -319,11 +687,10 @@ pub mod renderer {
         use anyhow::Result;
         use serde::Deserialize;
         use std::collections::HashMap;
-        use web_sys::HtmlImageElement;
 
         use crate::engine::renderer::Rect;
 
-        use super::Renderer;
+        use super::{ImageElement, Renderer};
 
         #[derive(Deserialize, Clone)]
         struct SheetRect {
@@ -338,6 +705,9 @@ pub mod renderer {
         pub struct Cell {
             frame: SheetRect,
             sprite_source_size: SheetRect,
+            // 切り抜き前のフレームの左上を原点とした当たり判定の矩形（省略可能）
+            #[serde(default)]
+            hitboxes: Vec<SheetRect>,
         }
 
         impl Cell {
@@ -350,6 +720,21 @@ pub mod renderer {
                 )
             }
 
+            // 切り抜き前のフレームの左上を (x, y) に置いたときの当たり判定
+            // 当たり判定が定義されていないセルは、描画される範囲全体を当たり判定とする
+            pub fn hitboxes_on_canvas(&self, x: i16, y: i16) -> Vec<Rect> {
+                if self.hitboxes.is_empty() {
+                    return vec![self.to_rect_on_canvas(x, y, self.width(), self.height())];
+                }
+
+                self.hitboxes
+                    .iter()
+                    .map(|hitbox| {
+                        Rect::new_from_x_y(x + hitbox.x, y + hitbox.y, hitbox.w, hitbox.h)
+                    })
+                    .collect()
+            }
+
             pub fn x(&self) -> i16 {
                 self.frame.x
             }
@@ -374,11 +759,11 @@ pub mod renderer {
 
         pub struct Sprite {
             sprite_sheet: SpriteSheet,
-            image: HtmlImageElement,
+            image: ImageElement,
         }
 
         impl Sprite {
-            pub fn new(sprite_sheet: SpriteSheet, image: HtmlImageElement) -> Self {
+            pub fn new(sprite_sheet: SpriteSheet, image: ImageElement) -> Self {
                 Self {
                     sprite_sheet,
                     image,
@@ -389,6 +774,15 @@ pub mod renderer {
                 self.sprite_sheet.frames.get(name)
             }
 
+            // cell の範囲のマスク（左上は Cell::to_rect_on_canvas の位置に置かれる）
+            #[cfg(feature = "pixel_collision")]
+            pub fn mask(&self, cell: &Cell) -> Option<std::rc::Rc<super::mask::AlphaMask>> {
+                super::mask::AlphaMask::cached(
+                    &self.image,
+                    &Rect::new_from_x_y(cell.x(), cell.y(), cell.width(), cell.height()),
+                )
+            }
+
             pub fn draw(
                 &self,
                 renderer: &Renderer,
@@ -401,83 +795,642 @@ pub mod renderer {
     }
 }
 
+// 移動する矩形と障害物の矩形の接触判定
+pub mod collision {
+    use super::renderer::{Point, Rect};
+
+    // 側面に触れた場合でも、上面からのめり込みがこの値以下なら上面に触れたとみなす
+    // （隣り合う矩形の継ぎ目に引っかからないようにするため）
+    const LANDING_TOLERANCE: f32 = 2.;
+
+    // 障害物のどの面に触れたか
+    #[derive(Clone, Copy, PartialEq)]
+    pub enum Side {
+        // 上面に上から触れた
+        Top,
+        // 下面に下から触れた
+        Bottom,
+        // 左側面に左から触れた
+        Left,
+        // 右側面に右から触れた
+        Right,
+    }
+
+    // 移動する矩形が障害物の矩形に触れたときの情報
+    pub struct Contact {
+        // 触れた時刻（直前のフレームの位置が 0、現在の位置が 1）
+        pub time: f32,
+        pub side: Side,
+        // 触れた障害物の矩形
+        pub surface: Rect,
+        // 現在の位置で重なっている部分（移動の途中で触れてすり抜けた場合は None）
+        pub overlap: Option<Rect>,
+        // 触れた面に垂直な方向のめり込みの深さ
+        pub penetration: i16,
+        // 直前のフレームですでに重なっていたかどうか
+        pub initially_overlapping: bool,
+    }
+
+    impl Contact {
+        // めり込みを解消するために、移動する矩形を動かす量
+        pub fn push_out(&self) -> Point {
+            match self.side {
+                Side::Top => Point {
+                    x: 0,
+                    y: -self.penetration,
+                },
+                Side::Bottom => Point {
+                    x: 0,
+                    y: self.penetration,
+                },
+                Side::Left => Point {
+                    x: -self.penetration,
+                    y: 0,
+                },
+                Side::Right => Point {
+                    x: self.penetration,
+                    y: 0,
+                },
+            }
+        }
+    }
+
+    // moving の各矩形と targets の各矩形との接触を、触れた時刻が早い順にすべて返す
+    // moving は直前のフレームから displacement だけ移動して現在の位置に来たものとする
+    pub fn contacts(moving: &[Rect], displacement: Point, targets: &[Rect]) -> Vec<Contact> {
+        let mut contacts: Vec<Contact> = moving
+            .iter()
+            .flat_map(|rect| {
+                targets
+                    .iter()
+                    .filter_map(move |target| sweep(rect, displacement, target))
+            })
+            .collect();
+        contacts.sort_by(|a, b| a.time.total_cmp(&b.time));
+        contacts
+    }
+
+    // moving が直前のフレームから displacement だけ移動する間に、target に最初に触れた時刻と面を返す
+    // 移動の途中で触れた場合も検出するので、速く動いていても障害物をすり抜けない
+    fn sweep(moving: &Rect, displacement: Point, target: &Rect) -> Option<Contact> {
+        let (dx, dy) = (displacement.x as f32, displacement.y as f32);
+        let start_x = (moving.x() - displacement.x) as f32;
+        let start_y = (moving.y() - displacement.y) as f32;
+        let (start_right, start_bottom) = (start_x + moving.w as f32, start_y + moving.h as f32);
+
+        let (x_entry, x_exit) = axis_times(
+            (start_x, start_right),
+            (target.x() as f32, target.right() as f32),
+            dx,
+        )?;
+        let (y_entry, y_exit) = axis_times(
+            (start_y, start_bottom),
+            (target.y() as f32, target.bottom() as f32),
+            dy,
+        )?;
+
+        let entry = x_entry.max(y_entry);
+        let exit = x_exit.min(y_exit);
+        if entry >= exit || entry > 1. || exit <= 0. {
+            return None;
+        }
+
+        let side = if entry < 0. {
+            // 直前のフレームですでに重なっていた場合は、めり込みが最も浅い面から触れたとみなす
+            [
+                (start_bottom - target.y() as f32, Side::Top),
+                (target.bottom() as f32 - start_y, Side::Bottom),
+                (start_right - target.x() as f32, Side::Left),
+                (target.right() as f32 - start_x, Side::Right),
+            ]
+            .into_iter()
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, side)| side)
+            .unwrap_or(Side::Top)
+        } else if y_entry >= x_entry {
+            if dy > 0. {
+                Side::Top
+            } else {
+                Side::Bottom
+            }
+        } else if dy >= 0. && start_bottom + dy * entry - target.y() as f32 <= LANDING_TOLERANCE {
+            Side::Top
+        } else if dx > 0. {
+            Side::Left
+        } else {
+            Side::Right
+        };
+
+        let penetration = match side {
+            Side::Top => moving.bottom() - target.y(),
+            Side::Bottom => target.bottom() - moving.y(),
+            Side::Left => moving.right() - target.x(),
+            Side::Right => target.right() - moving.x(),
+        };
+
+        Some(Contact {
+            time: entry.max(0.),
+            side,
+            surface: target.clone(),
+            overlap: moving.intersection(target),
+            penetration,
+            initially_overlapping: entry < 0.,
+        })
+    }
+
+    // 1 つの軸について、区間 a が速さ d で動くときに区間 b と重なり始める時刻と重ならなくなる時刻
+    // 動いていない軸で重なっていなければ、決して触れないので None を返す
+    fn axis_times(a: (f32, f32), b: (f32, f32), d: f32) -> Option<(f32, f32)> {
+        if d > 0. {
+            Some(((b.0 - a.1) / d, (b.1 - a.0) / d))
+        } else if d < 0. {
+            Some(((b.1 - a.0) / d, (b.0 - a.1) / d))
+        } else if a.0 < b.1 && a.1 > b.0 {
+            Some((f32::NEG_INFINITY, f32::INFINITY))
+        } else {
+            None
+        }
+    }
+}
+
+pub mod camera {
+    use std::f32::consts::TAU;
+
+    use super::renderer::{Point, Rect, Vector};
+
+    // 画面の揺れが 1 往復するフレーム数
+    const SHAKE_PERIOD: f32 = 4.;
+
+    // ワールド座標のどの範囲を画面に映すかを決めるカメラ
+    // ゲームオブジェクトはワールド座標に置いたまま動かさず、Renderer がカメラの位置だけずらして描画する
+    pub struct Camera {
+        // 画面の左上に映るワールド座標（滑らかに追従できるように小数で持つ）
+        position: Vector,
+        width: i16,
+        height: i16,
+        // 追従する対象がこの範囲（画面上の座標）の中にいる間はカメラを動かさない
+        dead_zone: Rect,
+        // 1 フレームで目標の位置に近づく割合（1 なら遅れずに追従する）
+        smoothing: f32,
+        // 画面の上端と下端が映してよいワールドの y 座標の範囲
+        vertical_limits: Option<(i16, i16)>,
+        // 一番右に進んだときの position.x から、左にどれだけ戻ってよいか
+        backtrack_limit: Option<f32>,
+        furthest_x: f32,
+        shake: Shake,
+    }
+
+    // 画面の揺れ
+    #[derive(Clone, Copy, Default)]
+    struct Shake {
+        magnitude: f32,
+        frames: u16,
+        remaining: u16,
+    }
+
+    impl Shake {
+        // 揺れの大きさは時間とともに小さくなる
+        fn amplitude(&self) -> f32 {
+            if self.remaining == 0 {
+                return 0.;
+            }
+            self.magnitude * self.remaining as f32 / self.frames as f32
+        }
+
+        fn offset(&self) -> Point {
+            let amplitude = self.amplitude();
+            let angle = self.remaining as f32 * TAU / SHAKE_PERIOD;
+            Point {
+                x: (amplitude * angle.sin()).round() as i16,
+                y: (amplitude * angle.cos()).round() as i16,
+            }
+        }
+    }
+
+    impl Camera {
+        // 左上がワールドの原点を映す width x height の画面
+        // 既定では対象が画面の中央から動くとすぐに追従する
+        pub fn new(width: i16, height: i16) -> Self {
+            Self {
+                position: Vector { x: 0., y: 0. },
+                width,
+                height,
+                dead_zone: Rect::new_from_x_y(width / 2, height / 2, 0, 0),
+                smoothing: 1.,
+                vertical_limits: None,
+                backtrack_limit: None,
+                furthest_x: 0.,
+                shake: Shake::default(),
+            }
+        }
+
+        pub fn with_dead_zone(mut self, dead_zone: Rect) -> Self {
+            self.dead_zone = dead_zone;
+            self
+        }
+
+        pub fn with_smoothing(mut self, smoothing: f32) -> Self {
+            self.smoothing = smoothing.clamp(0., 1.);
+            self
+        }
+
+        pub fn with_vertical_limits(mut self, top: i16, bottom: i16) -> Self {
+            self.vertical_limits = Some((top, bottom));
+            self.position.y = self.clamp_y(self.position.y);
+            self
+        }
+
+        pub fn with_backtrack_limit(mut self, distance: i16) -> Self {
+            self.backtrack_limit = Some(distance.max(0) as f32);
+            self
+        }
+
+        // target（ワールド座標）が不感帯の中に入るように 1 フレーム分動き、画面の揺れを進める
+        pub fn update(&mut self, target: Point) {
+            let x = follow(
+                self.position.x,
+                target.x,
+                self.dead_zone.x(),
+                self.dead_zone.right(),
+            );
+            let y = follow(
+                self.position.y,
+                target.y,
+                self.dead_zone.y(),
+                self.dead_zone.bottom(),
+            );
+            self.position.x =
+                self.clamp_x(self.position.x + (x - self.position.x) * self.smoothing);
+            self.furthest_x = self.furthest_x.max(self.position.x);
+            self.position.y =
+                self.clamp_y(self.position.y + (y - self.position.y) * self.smoothing);
+
+            self.shake.remaining = self.shake.remaining.saturating_sub(1);
+        }
+
+        // magnitude ピクセルの揺れを frames フレームの間起こす（すでに大きく揺れている場合はそちらを続ける）
+        pub fn shake(&mut self, magnitude: f32, frames: u16) {
+            if magnitude >= self.shake.amplitude() {
+                self.shake = Shake {
+                    magnitude,
+                    frames,
+                    remaining: frames,
+                };
+            }
+        }
+
+        pub fn is_shaking(&self) -> bool {
+            self.shake.remaining > 0
+        }
+
+        // ワールドの原点を移したときに、映している範囲が変わらないように dx だけ移動する
+        pub fn shift(&mut self, dx: i16) {
+            self.position.x += dx as f32;
+            self.furthest_x += dx as f32;
+        }
+
+        // 画面の左上に映すワールド座標（画面の揺れを含む）
+        pub fn origin(&self) -> Point {
+            let shake = self.shake.offset();
+            Point {
+                x: self.position.x.round() as i16 + shake.x,
+                y: self.position.y.round() as i16 + shake.y,
+            }
+        }
+
+        // 画面に映っているワールドの範囲（画面の揺れは含まない）
+        pub fn bounds(&self) -> Rect {
+            Rect::new_from_x_y(
+                self.position.x.round() as i16,
+                self.position.y.round() as i16,
+                self.width,
+                self.height,
+            )
+        }
+
+        pub fn is_visible(&self, rect: &Rect) -> bool {
+            self.bounds().intersects(rect)
+        }
+
+        // 右端が right のものが、左に戻れる限界よりも左にあり、もう画面に映ることがないか
+        pub fn is_left_behind(&self, right: i16) -> bool {
+            match self.backtrack_limit {
+                Some(distance) => (right as f32) < self.furthest_x - distance,
+                None => false,
+            }
+        }
+
+        pub fn to_screen(&self, point: Point) -> Point {
+            let origin = self.origin();
+            Point {
+                x: point.x - origin.x,
+                y: point.y - origin.y,
+            }
+        }
+
+        fn clamp_x(&self, x: f32) -> f32 {
+            match self.backtrack_limit {
+                Some(distance) => x.max(self.furthest_x - distance),
+                None => x,
+            }
+        }
+
+        fn clamp_y(&self, y: f32) -> f32 {
+            match self.vertical_limits {
+                Some((top, bottom)) => y.min((bottom - self.height) as f32).max(top as f32),
+                None => y,
+            }
+        }
+    }
+
+    // 1 つの軸について、target が画面上の start から end の間に映るカメラの位置のうち、position に最も近いもの
+    fn follow(position: f32, target: i16, start: i16, end: i16) -> f32 {
+        let on_screen = target as f32 - position;
+        if on_screen < start as f32 {
+            (target - start) as f32
+        } else if on_screen > end as f32 {
+            (target - end) as f32
+        } else {
+            position
+        }
+    }
+}
+
+pub mod viewport {
+    use super::renderer::Point;
+
+    // ゲームの画面（論理的な座標）をブラウザのウィンドウに合わせる方法
+    #[derive(Clone, Copy, Default, PartialEq, Debug)]
+    pub enum Fit {
+        // 縦横比を保ったまま、ウィンドウに収まる最大の大きさにする（余った部分は帯になる）
+        #[default]
+        Letterbox,
+        // 縦横比を変えて、ウィンドウ全体に引き伸ばす
+        Stretch,
+    }
+
+    // ゲームの width x height の画面を、ウィンドウのどこにどれだけの大きさで表示するか
+    // 大きさは CSS ピクセルで表示する大きさと、canvas が実際に持つピクセル数（devicePixelRatio 倍）を別に持つ
+    // canvas をウィンドウのどこに置くかはページの CSS に任せる
+    #[derive(Clone)]
+    pub struct Viewport {
+        width: i16,
+        height: i16,
+        fit: Fit,
+        // 直前の resize で受け取ったウィンドウの大きさと devicePixelRatio
+        window: Option<(f64, f64, f64)>,
+        // canvas を表示する大きさ（CSS ピクセル）
+        display_width: f64,
+        display_height: f64,
+        // canvas のピクセル数
+        backing_width: u32,
+        backing_height: u32,
+    }
+
+    impl Viewport {
+        // resize するまでは、ゲームの画面をそのままの大きさで表示する
+        pub fn new(width: i16, height: i16) -> Self {
+            let width = width.max(1);
+            let height = height.max(1);
+            Self {
+                width,
+                height,
+                fit: Fit::default(),
+                window: None,
+                display_width: width as f64,
+                display_height: height as f64,
+                backing_width: width as u32,
+                backing_height: height as u32,
+            }
+        }
+
+        pub fn with_fit(mut self, fit: Fit) -> Self {
+            self.fit = fit;
+            self
+        }
+
+        // ウィンドウの大きさ（CSS ピクセル）と devicePixelRatio から、表示する大きさを計算し直す
+        // 前回と同じ値なら何もせず false を返す
+        pub fn resize(
+            &mut self,
+            window_width: f64,
+            window_height: f64,
+            device_pixel_ratio: f64,
+        ) -> bool {
+            if self.window == Some((window_width, window_height, device_pixel_ratio)) {
+                return false;
+            }
+            self.window = Some((window_width, window_height, device_pixel_ratio));
+
+            // 非表示のタブなどで 0 や NaN が渡されても、大きさが 0 の canvas にはしない
+            let window_width = positive_or(window_width, 1.);
+            let window_height = positive_or(window_height, 1.);
+            let device_pixel_ratio = positive_or(device_pixel_ratio, 1.);
+
+            let scale_x = window_width / self.width as f64;
+            let scale_y = window_height / self.height as f64;
+            let (scale_x, scale_y) = match self.fit {
+                Fit::Letterbox => (scale_x.min(scale_y), scale_x.min(scale_y)),
+                Fit::Stretch => (scale_x, scale_y),
+            };
+
+            self.display_width = self.width as f64 * scale_x;
+            self.display_height = self.height as f64 * scale_y;
+            self.backing_width = ((self.display_width * device_pixel_ratio).round() as u32).max(1);
+            self.backing_height =
+                ((self.display_height * device_pixel_ratio).round() as u32).max(1);
+            true
+        }
+
+        pub fn fit(&self) -> Fit {
+            self.fit
+        }
+
+        // canvas を表示する大きさ（CSS ピクセル）
+        pub fn display_size(&self) -> (f64, f64) {
+            (self.display_width, self.display_height)
+        }
+
+        // canvas のピクセル数
+        pub fn backing_size(&self) -> (u32, u32) {
+            (self.backing_width, self.backing_height)
+        }
+
+        // ゲームの画面の 1 ピクセルに対応する canvas のピクセル数
+        // ゲームの画面がちょうど canvas 全体に映るように、丸めた後のピクセル数から求める
+        pub fn scale(&self) -> (f64, f64) {
+            (
+                self.backing_width as f64 / self.width as f64,
+                self.backing_height as f64 / self.height as f64,
+            )
+        }
+
+        // canvas の左上から測った位置（CSS ピクセル）を、ゲームの画面上の座標に変換する
+        // canvas の外であれば None を返す
+        pub fn to_logical(&self, x: f64, y: f64) -> Option<Point> {
+            let x = x * self.width as f64 / self.display_width;
+            let y = y * self.height as f64 / self.display_height;
+            if !(0. ..self.width as f64).contains(&x) || !(0. ..self.height as f64).contains(&y) {
+                return None;
+            }
+            Some(Point {
+                x: x.floor() as i16,
+                y: y.floor() as i16,
+            })
+        }
+    }
+
+    fn positive_or(value: f64, default: f64) -> f64 {
+        if value > 0. {
+            value
+        } else {
+            default
+        }
+    }
+}
+
 pub mod key_state {
     use anyhow::Result;
     use futures::channel::mpsc::{unbounded, UnboundedReceiver};
-    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+    use std::{cell::RefCell, collections::HashSet, rc::Rc};
     use wasm_bindgen::JsCast;
 
+    use super::{renderer::Point, viewport::Viewport};
     use crate::browser;
 
+    // キーの code（"ArrowUp" など）で表したキー入力と、マウスやタッチで canvas を押した入力
+    // ブラウザの KeyboardEvent は入力を受け取った時点で code に変換し、KeyState はブラウザに依存しない
     pub(super) enum KeyPress {
-        KeyUp(web_sys::KeyboardEvent),
-        KeyDown(web_sys::KeyboardEvent),
+        KeyUp(String),
+        KeyDown(String),
+        // 押した位置（canvas の左上から測った CSS ピクセル）
+        PointerDown(f64, f64),
+        PointerUp,
     }
 
     pub(super) fn prepare_input() -> Result<UnboundedReceiver<KeyPress>> {
         let (keyevent_tx, keyevent_rx) = unbounded();
         let keydown_tx = Rc::new(RefCell::new(keyevent_tx));
         let keyup_tx = keydown_tx.clone();
+        let pointerdown_tx = keydown_tx.clone();
+        let pointerup_tx = keydown_tx.clone();
 
         let canvas = browser::canvas().expect("Canvas not found");
 
         let onkeydown = browser::create_wasm_closure(move |keycode: web_sys::KeyboardEvent| {
             keydown_tx
                 .borrow_mut()
-                .start_send(KeyPress::KeyDown(keycode));
+                .start_send(KeyPress::KeyDown(keycode.code()));
         });
         canvas.set_onkeydown(Some(onkeydown.as_ref().unchecked_ref()));
 
         let onkeyup = browser::create_wasm_closure(move |keycode: web_sys::KeyboardEvent| {
-            keyup_tx.borrow_mut().start_send(KeyPress::KeyUp(keycode));
+            keyup_tx
+                .borrow_mut()
+                .start_send(KeyPress::KeyUp(keycode.code()));
         });
         canvas.set_onkeyup(Some(onkeyup.as_ref().unchecked_ref()));
 
+        // マウスとタッチは PointerEvent でまとめて受け取る
+        // canvas がページのどこに置かれていても合うように、押した時点の canvas の位置から測る
+        let pointer_canvas = canvas.clone();
+        let onpointerdown = browser::create_wasm_closure(move |event: web_sys::MouseEvent| {
+            let rect = pointer_canvas.get_bounding_client_rect();
+            let _ = pointerdown_tx
+                .borrow_mut()
+                .start_send(KeyPress::PointerDown(
+                    event.client_x() as f64 - rect.left(),
+                    event.client_y() as f64 - rect.top(),
+                ));
+        });
+        canvas.set_onpointerdown(Some(onpointerdown.as_ref().unchecked_ref()));
+
+        // 押したまま canvas の外に出たときも、離したことにする
+        let onpointerup = browser::create_wasm_closure(move |_: web_sys::MouseEvent| {
+            let _ = pointerup_tx.borrow_mut().start_send(KeyPress::PointerUp);
+        });
+        canvas.set_onpointerup(Some(onpointerup.as_ref().unchecked_ref()));
+        canvas.set_onpointercancel(Some(onpointerup.as_ref().unchecked_ref()));
+        canvas.set_onpointerleave(Some(onpointerup.as_ref().unchecked_ref()));
+
         onkeydown.forget();
         onkeyup.forget();
+        onpointerdown.forget();
+        onpointerup.forget();
 
         Ok(keyevent_rx)
     }
 
+    // canvas を押した位置は、viewport でゲームの画面上の座標に直してから KeyState に渡す
+    // prepare_input で canvas に登録した入力の受け取りをやめる
+    pub(super) fn release_input() -> Result<()> {
+        let canvas = browser::canvas()?;
+        canvas.set_onkeydown(None);
+        canvas.set_onkeyup(None);
+        canvas.set_onpointerdown(None);
+        canvas.set_onpointerup(None);
+        canvas.set_onpointercancel(None);
+        canvas.set_onpointerleave(None);
+        Ok(())
+    }
+
     pub(super) fn process_input(
         state: &mut KeyState,
         keyevent_receiver: &mut UnboundedReceiver<KeyPress>,
+        viewport: &Viewport,
     ) {
         loop {
             match keyevent_receiver.try_next() {
                 Ok(None) => break,
                 Err(_) => break,
                 Ok(Some(event)) => match event {
-                    KeyPress::KeyDown(keyboard_event) => {
-                        state.set_pressed(&keyboard_event.code(), keyboard_event)
+                    KeyPress::KeyDown(code) => state.set_pressed(&code),
+                    KeyPress::KeyUp(code) => state.set_released(&code),
+                    KeyPress::PointerDown(x, y) => {
+                        if let Some(point) = viewport.to_logical(x, y) {
+                            state.set_pointer_pressed(point);
+                        }
                     }
-                    KeyPress::KeyUp(keyboard_event) => state.set_released(&keyboard_event.code()),
+                    KeyPress::PointerUp => state.set_pointer_released(),
                 },
             }
         }
     }
 
+    #[derive(Default)]
     pub struct KeyState {
-        pressed_keys: HashMap<String, web_sys::KeyboardEvent>,
+        pressed_keys: HashSet<String>,
+        // マウスやタッチで押している、ゲームの画面上の位置
+        pointer: Option<Point>,
     }
 
     impl KeyState {
-        pub(super) fn new() -> Self {
-            Self {
-                pressed_keys: HashMap::new(),
-            }
+        pub fn new() -> Self {
+            Self::default()
         }
 
         pub fn is_pressed(&self, keycode: &str) -> bool {
-            self.pressed_keys.contains_key(keycode)
+            self.pressed_keys.contains(keycode)
         }
 
-        fn set_pressed(&mut self, keycode: &str, event: web_sys::KeyboardEvent) {
-            self.pressed_keys.insert(keycode.to_string(), event);
+        pub fn set_pressed(&mut self, keycode: &str) {
+            self.pressed_keys.insert(keycode.to_string());
         }
 
-        fn set_released(&mut self, keycode: &str) {
+        pub fn set_released(&mut self, keycode: &str) {
             self.pressed_keys.remove(keycode);
         }
+
+        pub fn pointer(&self) -> Option<Point> {
+            self.pointer
+        }
+
+        pub fn set_pointer_pressed(&mut self, point: Point) {
+            self.pointer = Some(point);
+        }
+
+        pub fn set_pointer_released(&mut self) {
+            self.pointer = None;
+        }
     }
 }
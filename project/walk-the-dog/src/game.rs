@@ -3,75 +3,190 @@ use std::rc::Rc;
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use rand::{thread_rng, Rng};
-use web_sys::HtmlImageElement;
 
 use crate::{
     engine::{
+        camera::Camera,
         key_state::KeyState,
-        renderer::{sprite::Sprite, Point, Rect, Renderer},
+        renderer::{sprite::Sprite, ImageElement, Point, Rect, Renderer},
+        viewport::Viewport,
         Game,
     },
-    segments::{rightmost, stone_and_high_platform, two_stone_and_low_platform},
+    segments::{
+        bobbing_stone, bridge_over_pit, moving_bridge_over_pit, overhead_beam, patrolling_slime,
+        pit, rightmost, stone_and_high_platform, swooping_bat, two_stone_and_low_platform,
+    },
 };
 
+#[cfg(feature = "physics_debug")]
+use self::physics::panel::PhysicsPanel;
+#[cfg(feature = "history_debug")]
+use self::rhb::HistoryOverlay;
 use self::{
     background::Background,
-    objects::{platform::Platform, GameObject, Obstacle},
+    objects::{
+        enemy::Enemy, item::Item, platform::Platform, Collectible, Collision, GameObject, Obstacle,
+        Reward,
+    },
+    physics::PhysicsConfig,
+    power_up::{PowerUp, SLOW_MOTION_SCALE},
     rhb::{RedHatBoy, FLOOR, STARTING_POINT},
 };
 
-mod background;
+pub mod background;
 pub mod bounding_box;
+pub mod motion;
 pub mod objects;
-mod rhb;
+pub mod physics;
+pub mod power_up;
+pub mod rhb;
+
+pub use self::rhb::{history_json, state_diagram, DiagramFormat};
 
 use objects::stone::Stone;
 
+// ゲームの画面の大きさ（ウィンドウに合わせて拡大・縮小して表示する）
 const WIDTH: i16 = 600;
 const HEIGHT: i16 = 600;
 
+// カメラの左端からこの距離までは、次のセグメントを生成しておく
 const TIMELINE_MINIMUM: i16 = 1000;
-const OBSTACLE_BUFFER: i16 = 20;
+
+// rhb がこの範囲（画面上の座標）の中にいる間は、カメラを動かさない
+const CAMERA_DEAD_ZONE: Rect = Rect::new_from_x_y(40, 0, 120, HEIGHT);
+const CAMERA_SMOOTHING: f32 = 0.2;
+// カメラが一番右に進んだ位置から、左に戻れる距離
+const CAMERA_BACKTRACK: i16 = WIDTH / 2;
+// rhb が倒されたときの画面の揺れ
+const DEFEAT_SHAKE: f32 = 8.;
+const DEFEAT_SHAKE_FRAMES: u16 = 20;
+// 座標は i16 なので、カメラがこの位置より右に進んだら、ワールドの原点をカメラの位置に移す
+const ORIGIN_SHIFT_THRESHOLD: i16 = 16384;
+
+// スコアを表示する位置
+const SCORE_POSITION: Point = Point { x: 10, y: 30 };
+// 効果中のパワーアップを表示する行の間隔
+const HUD_LINE_HEIGHT: i16 = 30;
+
+// 敵を踏みつけたときに得られるスコア
+const STOMP_SCORE: u32 = 100;
 
 pub enum WalkTheDog {
     Loading,
-    Loaded(Walk),
+    Loaded(Box<Walk>),
 }
 
 pub struct Walk {
     rhb: RedHatBoy,
     background: Background,
     obstacles: Vec<Box<dyn Obstacle>>,
+    collectibles: Vec<Box<dyn Collectible>>,
+    enemies: Vec<Enemy>,
     obstacle_sheet: Rc<Sprite>,
-    stone: HtmlImageElement,
+    item_sheet: Rc<Sprite>,
+    enemy_sheet: Rc<Sprite>,
+    stone: ImageElement,
+    // 生成済みのセグメントの右端
     timeline: i16,
+    camera: Camera,
+    score: u32,
+    // デバッグ用パネルで調整中の物理パラメータ
+    #[cfg(feature = "physics_debug")]
+    physics: PhysicsConfig,
+    #[cfg(feature = "physics_debug")]
+    physics_panel: PhysicsPanel,
+    #[cfg(feature = "history_debug")]
+    history_overlay: HistoryOverlay,
 }
 
 impl Walk {
-    fn velocity(&self) -> i16 {
-        -self.rhb.walking_speed()
+    // ワールドの原点を移して、すべてのゲームオブジェクトとカメラを x 方向に dx だけ移動させる
+    fn shift_origin(&mut self, dx: i16) {
+        self.rhb.shift(dx);
+        self.background.shift(dx);
+        self.obstacles
+            .iter_mut()
+            .for_each(|obstacle| obstacle.shift(dx));
+        self.collectibles
+            .iter_mut()
+            .for_each(|collectible| collectible.shift(dx));
+        self.enemies.iter_mut().for_each(|enemy| enemy.shift(dx));
+        self.camera.shift(dx);
+        self.timeline += dx;
     }
 
     fn generate_next_segment(&mut self) {
         let mut rng = thread_rng();
-        let next_segment = rng.gen_range(0..2);
+        let next_segment = rng.gen_range(0..9);
 
-        let mut next_obstacles = match next_segment {
+        // 地面が途切れないように、セグメントは直前のセグメントの右端から隙間なく並べる
+        let mut next_segment = match next_segment {
             0 => stone_and_high_platform(
                 self.stone.clone(),
                 self.obstacle_sheet.clone(),
-                self.timeline + OBSTACLE_BUFFER,
+                self.item_sheet.clone(),
+                self.timeline,
             ),
             1 => two_stone_and_low_platform(
                 self.stone.clone(),
                 self.obstacle_sheet.clone(),
-                self.timeline + OBSTACLE_BUFFER,
+                self.item_sheet.clone(),
+                self.timeline,
+            ),
+            2 => pit(
+                self.obstacle_sheet.clone(),
+                self.item_sheet.clone(),
+                self.timeline,
+            ),
+            3 => bridge_over_pit(
+                self.obstacle_sheet.clone(),
+                self.item_sheet.clone(),
+                self.timeline,
+            ),
+            4 => moving_bridge_over_pit(self.obstacle_sheet.clone(), self.timeline),
+            5 => bobbing_stone(
+                self.stone.clone(),
+                self.obstacle_sheet.clone(),
+                self.item_sheet.clone(),
+                self.timeline,
+            ),
+            6 => patrolling_slime(
+                self.obstacle_sheet.clone(),
+                self.enemy_sheet.clone(),
+                self.timeline,
+            ),
+            7 => swooping_bat(
+                self.obstacle_sheet.clone(),
+                self.enemy_sheet.clone(),
+                self.timeline,
             ),
-            _ => vec![],
+            // 次のセグメントは障害物の右端から並べるので、障害物のないセグメントは作らない
+            _ => overhead_beam(self.obstacle_sheet.clone(), self.timeline),
         };
 
-        self.timeline = rightmost(&next_obstacles);
-        self.obstacles.append(&mut next_obstacles);
+        self.timeline = rightmost(&next_segment.obstacles);
+        self.obstacles.append(&mut next_segment.obstacles);
+        self.collectibles.append(&mut next_segment.collectibles);
+        self.enemies.append(&mut next_segment.enemies);
+    }
+}
+
+// 衝突の結果を rhb とスコアに反映する
+fn resolve_collision(rhb: &mut RedHatBoy, score: &mut u32, collision: Collision) {
+    match collision {
+        Collision::Harm => rhb.hit(),
+        Collision::LandOn { y, carry } => {
+            rhb.land_on(y);
+            rhb.carry(carry);
+        }
+        Collision::Collect(Reward::Score(points)) => *score += points,
+        Collision::Collect(Reward::PowerUp(power_up)) => rhb.power_up(power_up),
+        Collision::Stomp => {
+            rhb.bounce();
+            *score += STOMP_SCORE;
+        }
+        Collision::PushOut(offset) => rhb.push_out(offset),
+        Collision::Bonk => rhb.bonk(),
     }
 }
 
@@ -81,69 +196,174 @@ impl WalkTheDog {
     }
 }
 
+impl Default for WalkTheDog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[async_trait(?Send)]
 impl Game for WalkTheDog {
     async fn initialize(&self) -> Result<Box<dyn Game>> {
         match self {
             Self::Loading => {
                 let rhb_sprite = RedHatBoy::load_sprite().await?;
+                let physics = RedHatBoy::fit_physics(&rhb_sprite, PhysicsConfig::load().await?)?;
                 let rhb = RedHatBoy::new(
                     rhb_sprite,
                     Point {
                         x: STARTING_POINT,
                         y: FLOOR,
                     },
+                    physics,
                 );
 
-                let background = Background::new().await?;
+                let camera = Camera::new(WIDTH, HEIGHT)
+                    .with_dead_zone(CAMERA_DEAD_ZONE)
+                    .with_smoothing(CAMERA_SMOOTHING)
+                    .with_backtrack_limit(CAMERA_BACKTRACK)
+                    .with_vertical_limits(0, HEIGHT);
+
+                let background = Background::load(camera.bounds()).await?;
 
                 let stone_image = Stone::load_image().await?;
 
                 let platform_sprite = Platform::load_sprite().await?;
 
-                let obstacles =
-                    two_stone_and_low_platform(stone_image.clone(), platform_sprite.clone(), 0);
-                let timeline = rightmost(&obstacles);
+                let item_sprite = Item::load_sprite().await?;
 
-                Ok(Box::new(WalkTheDog::Loaded(Walk {
+                let enemy_sprite = Enemy::load_sprite().await?;
+
+                let segment = two_stone_and_low_platform(
+                    stone_image.clone(),
+                    platform_sprite.clone(),
+                    item_sprite.clone(),
+                    0,
+                );
+                let timeline = rightmost(&segment.obstacles);
+
+                Ok(Box::new(WalkTheDog::Loaded(Box::new(Walk {
                     rhb,
                     background,
-                    obstacles,
+                    obstacles: segment.obstacles,
+                    collectibles: segment.collectibles,
+                    enemies: segment.enemies,
                     obstacle_sheet: platform_sprite,
+                    item_sheet: item_sprite,
+                    enemy_sheet: enemy_sprite,
                     stone: stone_image,
                     timeline,
-                })))
+                    camera,
+                    score: 0,
+                    #[cfg(feature = "physics_debug")]
+                    physics,
+                    #[cfg(feature = "physics_debug")]
+                    physics_panel: PhysicsPanel::default(),
+                    #[cfg(feature = "history_debug")]
+                    history_overlay: HistoryOverlay::default(),
+                }))))
             }
             Self::Loaded(_) => Err(anyhow!("Error: Game is already initialized")),
         }
     }
 
     fn update(&mut self, keystate: &KeyState) {
+        // この update 1 回が表す実時間のフレーム数
+        let real_frames = 1. / self.time_scale();
         match self {
             Self::Loading => {}
             Self::Loaded(walk) => {
-                let velocity = walk.velocity();
-
+                let was_defeated = walk.rhb.is_defeated();
                 walk.rhb.update();
+                walk.rhb.elapse_power_ups(real_frames);
 
-                walk.background.update(velocity);
+                // ゲームオブジェクトはワールド座標に置いたまま動かさず、カメラがもう映すことのないものを削除する
+                // 左に走って戻ってもよいように、カメラが左に戻れる限界より左に取り残されたものだけを削除する
+                let view = walk.camera.bounds();
+                let camera = &walk.camera;
 
-                // 画面外に出た障害物を削除する
                 walk.obstacles
-                    .retain(|obstacle| obstacle.bounding_box().right() > 0);
+                    .retain(|obstacle| !camera.is_left_behind(obstacle.bounding_box().right()));
 
                 walk.obstacles.iter_mut().for_each(|obstacle| {
-                    obstacle.update_position(velocity);
-                    obstacle.check_intersection(&mut walk.rhb);
+                    obstacle.update_motion();
+                    for collision in obstacle.check_intersection(&walk.rhb) {
+                        resolve_collision(&mut walk.rhb, &mut walk.score, collision);
+                    }
+                });
+
+                // 取り残されたアイテムと、獲得したアイテムを削除する
+                walk.collectibles.retain(|collectible| {
+                    !camera.is_left_behind(collectible.bounding_box().right())
+                });
+                let magnet = walk
+                    .rhb
+                    .has_power_up(PowerUp::Magnet)
+                    .then(|| walk.rhb.center());
+                walk.collectibles.retain_mut(|collectible| {
+                    collectible.update();
+                    if let Some(target) = magnet {
+                        collectible.attract_to(target);
+                    }
+                    let collisions = collectible.check_intersection(&walk.rhb);
+                    let collected = !collisions.is_empty();
+                    for collision in collisions {
+                        resolve_collision(&mut walk.rhb, &mut walk.score, collision);
+                    }
+                    !collected
                 });
 
-                if walk.timeline < TIMELINE_MINIMUM {
+                // 取り残された敵と、倒された後の演出が終わった敵を削除する
+                walk.enemies.retain(|enemy| {
+                    !camera.is_left_behind(enemy.bounding_box().right()) && !enemy.is_gone()
+                });
+                let target = walk.rhb.center();
+                walk.enemies.iter_mut().for_each(|enemy| {
+                    enemy.update(target);
+                    for collision in enemy.check_intersection(&walk.rhb) {
+                        if let Collision::Stomp = collision {
+                            enemy.defeat();
+                        }
+                        resolve_collision(&mut walk.rhb, &mut walk.score, collision);
+                    }
+                });
+
+                // 穴に落ちてカメラの下に出たらゲームオーバー
+                if walk.rhb.pos_y() > view.bottom() {
+                    walk.rhb.knock_out();
+                }
+                // カメラが左に戻れる限界まで来たら、それより左には行けないように押し戻す
+                let left = walk.rhb.bounding_box().left();
+                if left < view.x() {
+                    walk.rhb.push_out(Point {
+                        x: view.x() - left,
+                        y: 0,
+                    });
+                }
+                if !was_defeated && walk.rhb.is_defeated() {
+                    walk.camera.shake(DEFEAT_SHAKE, DEFEAT_SHAKE_FRAMES);
+                }
+
+                walk.camera.update(walk.rhb.center());
+                // 背景は、このフレームでカメラが動いた分だけ動かす
+                walk.background.update(&walk.camera.bounds());
+
+                if walk.timeline < walk.camera.bounds().x() + TIMELINE_MINIMUM {
                     walk.generate_next_segment();
-                } else {
-                    walk.timeline += velocity;
                 }
 
-                if keystate.is_pressed("ArrowRight") {
+                let origin = walk.camera.bounds().x();
+                if origin > ORIGIN_SHIFT_THRESHOLD {
+                    walk.shift_origin(-origin);
+                }
+
+                // マウスやタッチでも遊べるように、画面を押すと右に走り出し、
+                // 上半分を押している間はジャンプ、下半分を押すとスライディングする
+                let pointer = keystate.pointer();
+                let pointer_up = pointer.is_some_and(|point| point.y < HEIGHT / 2);
+                let pointer_down = pointer.is_some_and(|point| point.y >= HEIGHT / 2);
+
+                if keystate.is_pressed("ArrowRight") || pointer.is_some() {
                     walk.rhb.run_right();
                 }
 
@@ -151,13 +371,23 @@ impl Game for WalkTheDog {
                     walk.rhb.run_left();
                 }
 
-                if keystate.is_pressed("ArrowDown") {
+                if keystate.is_pressed("ArrowDown") || pointer_down {
                     walk.rhb.slide();
                 }
 
-                if keystate.is_pressed("ArrowUp") {
+                if keystate.is_pressed("ArrowUp") || pointer_up {
                     walk.rhb.jump();
+                } else {
+                    walk.rhb.release_jump();
+                }
+
+                #[cfg(feature = "physics_debug")]
+                if walk.physics_panel.update(keystate, &mut walk.physics) {
+                    walk.rhb.set_physics(walk.physics);
                 }
+
+                #[cfg(feature = "history_debug")]
+                walk.history_overlay.update(keystate);
             }
         }
     }
@@ -165,22 +395,80 @@ impl Game for WalkTheDog {
     fn draw(&self, renderer: &Renderer) {
         match self {
             WalkTheDog::Loading => {}
-            WalkTheDog::Loaded(Walk {
-                rhb,
-                background,
-                obstacles,
-                obstacle_sheet: _,
-                stone: _,
-                timeline: _,
-            }) => {
+            WalkTheDog::Loaded(walk) => {
+                let Walk {
+                    rhb,
+                    background,
+                    obstacles,
+                    collectibles,
+                    enemies,
+                    obstacle_sheet: _,
+                    item_sheet: _,
+                    enemy_sheet: _,
+                    stone: _,
+                    timeline: _,
+                    camera,
+                    score,
+                    // デバッグ用のパネルとオーバーレイは最後に描画する
+                    ..
+                } = walk.as_ref();
+
                 renderer.clear(&Rect::new_from_x_y(0, 0, WIDTH, HEIGHT));
 
+                renderer.set_camera(camera).expect("Error applying camera");
                 background.draw(renderer).expect("Error drawing background");
                 rhb.draw(renderer).expect("Error drawing red hat boy");
                 obstacles
                     .iter()
                     .for_each(|obstacle| obstacle.draw(renderer).expect("Error drawing obstacle"));
+                collectibles.iter().for_each(|collectible| {
+                    collectible
+                        .draw(renderer)
+                        .expect("Error drawing collectible")
+                });
+                enemies
+                    .iter()
+                    .for_each(|enemy| enemy.draw(renderer).expect("Error drawing enemy"));
+
+                // スコアやデバッグ用の表示は、カメラに関係なく画面に固定する
+                renderer.reset_camera().expect("Error resetting camera");
+                renderer.draw_text(&format!("SCORE: {}", score), &SCORE_POSITION);
+
+                // 効果中のパワーアップと残り秒数をスコアの下に並べる
+                PowerUp::ALL
+                    .iter()
+                    .filter(|power_up| rhb.has_power_up(**power_up))
+                    .enumerate()
+                    .for_each(|(line, power_up)| {
+                        let seconds = (rhb.power_up_remaining(*power_up) as f32 / 60.).ceil();
+                        renderer.draw_text(
+                            &format!("{} {}s", power_up.label(), seconds),
+                            &Point {
+                                x: SCORE_POSITION.x,
+                                y: SCORE_POSITION.y + HUD_LINE_HEIGHT * (line as i16 + 1),
+                            },
+                        );
+                    });
+
+                #[cfg(feature = "physics_debug")]
+                walk.physics_panel.draw(renderer, &walk.physics);
+
+                #[cfg(feature = "history_debug")]
+                walk.history_overlay.draw(renderer, &rhb.history());
+            }
+        }
+    }
+
+    fn viewport(&self) -> Viewport {
+        Viewport::new(WIDTH, HEIGHT)
+    }
+
+    fn time_scale(&self) -> f32 {
+        match self {
+            WalkTheDog::Loaded(walk) if walk.rhb.has_power_up(PowerUp::SlowMotion) => {
+                SLOW_MOTION_SCALE
             }
+            _ => 1.,
         }
     }
 }
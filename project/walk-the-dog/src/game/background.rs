@@ -1,61 +1,211 @@
 use anyhow::Result;
+use gloo_utils::format::JsValueSerdeExt;
+use serde::Deserialize;
 
-use crate::engine::renderer::{
-    image::{self, Image},
-    Point, Renderer,
-};
+use crate::browser;
+use crate::engine::renderer::{image, ImageElement, Point, Rect, Renderer, Vector};
 
-pub struct Background {
-    images: [Image; 2],
+// 背景の設定（background.json）
+// レイヤーは奥にあるものから順に並べ、その順に描画する
+#[derive(Deserialize)]
+pub struct BackgroundConfig {
+    pub layers: Vec<LayerConfig>,
 }
 
-impl Background {
-    pub async fn new() -> Result<Self> {
-        let image = image::load_image("BG.png").await?;
-        let image1 = Image::new(image.clone(), Point { x: 0, y: 0 });
-        let image2 = Image::new(
+#[derive(Deserialize)]
+pub struct LayerConfig {
+    pub image: String,
+    // カメラが動いた距離に対してレイヤーが動く割合（0 なら動かず、1 なら障害物と同じ速さで動く）
+    #[serde(default = "LayerConfig::default_scroll_factor")]
+    pub scroll_factor: f32,
+    // 画面の上端からレイヤーの上端までの距離
+    #[serde(default)]
+    pub offset_y: i16,
+    #[serde(default)]
+    pub tiling: Tiling,
+}
+
+impl LayerConfig {
+    fn default_scroll_factor() -> f32 {
+        1.
+    }
+}
+
+// レイヤーの画像の並べ方
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Tiling {
+    // 横に繰り返して並べる
+    #[default]
+    Horizontal,
+    // 縦に繰り返して並べる
+    Vertical,
+    // 縦横に繰り返して敷き詰める
+    Both,
+    // 1 枚だけ置く（太陽など）
+    Single,
+}
+
+impl Tiling {
+    fn repeats_x(&self) -> bool {
+        matches!(self, Tiling::Horizontal | Tiling::Both)
+    }
+
+    fn repeats_y(&self) -> bool {
+        matches!(self, Tiling::Vertical | Tiling::Both)
+    }
+}
+
+impl BackgroundConfig {
+    pub async fn load() -> Result<Self> {
+        let json = browser::fetch_json("background.json").await?;
+        let config: BackgroundConfig = json.into_serde()?;
+
+        Ok(config)
+    }
+}
+
+// 奥行きの異なる背景の 1 枚
+// 画面上での画像の位置を持ち、カメラが動いた距離に scroll_factor を掛けた分だけ動かす
+// 繰り返す方向の位置は画像の大きさで割った余りにしておき、描画するときに画面を覆う枚数だけ並べる
+pub struct Layer {
+    image: ImageElement,
+    scroll_factor: f32,
+    tiling: Tiling,
+    // 画面上での画像の左上（1 ピクセル未満の移動量も失わないように小数で持つ）
+    offset: Vector,
+}
+
+impl Layer {
+    pub fn new(config: &LayerConfig, image: ImageElement) -> Self {
+        let mut layer = Self {
             image,
-            Point {
-                x: image1.width(),
-                y: 0,
+            scroll_factor: config.scroll_factor,
+            tiling: config.tiling,
+            offset: Vector {
+                x: 0.,
+                y: config.offset_y as f32,
             },
-        );
-        Ok(Self {
-            images: [image1, image2],
-        })
+        };
+        layer.wrap();
+        layer
     }
 
-    pub fn draw(&self, renderer: &Renderer) -> Result<()> {
-        self.images
-            .iter()
-            .map(|image| image.draw(renderer))
-            .collect::<Result<Vec<_>, _>>()?;
+    // 画面上の width x height の範囲を覆うように並べた、画像の位置と大きさ
+    pub fn tiles(&self, width: i16, height: i16) -> Vec<Rect> {
+        let (tile_width, tile_height) = self.tile_size();
+        let columns = cover(self.offset.x, tile_width, width, self.tiling.repeats_x());
+        let rows = cover(self.offset.y, tile_height, height, self.tiling.repeats_y());
+
+        rows.iter()
+            .flat_map(|y| {
+                columns
+                    .iter()
+                    .map(move |x| Rect::new_from_x_y(*x, *y, tile_width, tile_height))
+            })
+            .collect()
+    }
+
+    // カメラが (dx, dy) だけ動いたときに、レイヤーを画面上で動かす
+    fn scroll(&mut self, dx: i16, dy: i16) {
+        self.offset.x -= dx as f32 * self.scroll_factor;
+        self.offset.y -= dy as f32 * self.scroll_factor;
+        self.wrap();
+    }
 
-        Ok(())
+    // 繰り返す方向は、どれだけ速く・どちら向きに動いても画像 1 枚分の範囲に収める
+    fn wrap(&mut self) {
+        let (tile_width, tile_height) = self.tile_size();
+        if self.tiling.repeats_x() {
+            self.offset.x = self.offset.x.rem_euclid(tile_width as f32);
+        }
+        if self.tiling.repeats_y() {
+            self.offset.y = self.offset.y.rem_euclid(tile_height as f32);
+        }
     }
 
-    pub fn update(&mut self, velocity: i16) {
-        self.images.iter_mut().for_each(|image| {
-            image.move_horizontally(velocity);
-        });
+    // 大きさが 0 の画像（読み込みに失敗したものなど）でも、並べる枚数が無限にならないようにする
+    fn tile_size(&self) -> (i16, i16) {
+        (
+            (self.image.width() as i16).max(1),
+            (self.image.height() as i16).max(1),
+        )
+    }
+}
 
-        // 画像が画面の左端から外に出たら、右端に移動する
-        let len = self.images.len();
-        let right_position_list = self
-            .images
-            .iter()
-            .map(|image| image.right())
-            .collect::<Vec<_>>();
+// 1 つの軸について、offset の位置にある画像を繰り返して 0 から length までを覆うときの、各画像の位置
+// 繰り返さない場合は offset の位置の 1 枚だけを返す
+fn cover(offset: f32, size: i16, length: i16, repeats: bool) -> Vec<i16> {
+    let offset = offset.round() as i16;
+    if !repeats {
+        return vec![offset];
+    }
 
-        let image_out_of_game = self
-            .images
-            .iter_mut()
-            .enumerate()
-            .find(|(_, image)| image.right() < 0);
+    // offset は 0 以上 size 以下なので、1 枚左（上）から並べ始めれば 0 の位置も覆える
+    let first = if offset > 0 { offset - size } else { offset };
+    (0..)
+        .map(|index| first + index * size)
+        .take_while(|position| *position < length)
+        .collect()
+}
+
+pub struct Background {
+    layers: Vec<Layer>,
+    // 直前の update でカメラに映っていた範囲（ワールド座標）
+    view: Rect,
+}
+
+impl Background {
+    pub fn new(layers: Vec<Layer>, view: Rect) -> Self {
+        Self { layers, view }
+    }
 
-        if let Some((index, image)) = image_out_of_game {
-            let new_x = right_position_list[(index + len - 1) % len];
-            image.set_x(new_x);
+    pub async fn load(view: Rect) -> Result<Self> {
+        let config = BackgroundConfig::load().await?;
+
+        let mut layers = vec![];
+        for layer in &config.layers {
+            let image = image::load_image(&layer.image).await?;
+            layers.push(Layer::new(layer, image));
         }
+
+        Ok(Self::new(layers, view))
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    // カメラに映る範囲の左上に画面上の座標を足して、ワールド座標で描画する
+    pub fn draw(&self, renderer: &Renderer) -> Result<()> {
+        self.layers.iter().try_for_each(|layer| {
+            layer
+                .tiles(self.view.w, self.view.h)
+                .iter()
+                .try_for_each(|tile| {
+                    renderer.draw_entire_image(
+                        &layer.image,
+                        &Point {
+                            x: self.view.x() + tile.x(),
+                            y: self.view.y() + tile.y(),
+                        },
+                    )
+                })
+        })
+    }
+
+    // view（カメラに映っている範囲）が前のフレームから動いた分だけ、各レイヤーを動かす
+    pub fn update(&mut self, view: &Rect) {
+        let dx = view.x() - self.view.x();
+        let dy = view.y() - self.view.y();
+        self.view = view.clone();
+        self.layers
+            .iter_mut()
+            .for_each(|layer| layer.scroll(dx, dy));
+    }
+
+    // ワールドの原点を移したときは、カメラと一緒に移動したのでレイヤーは動かさない
+    pub fn shift(&mut self, dx: i16) {
+        self.view.set_x(self.view.x() + dx);
     }
 }
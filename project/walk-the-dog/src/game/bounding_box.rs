@@ -1,32 +1,24 @@
+use crate::engine::collision::{self, Contact};
+use crate::engine::renderer::{Point, Rect, Renderer};
 use anyhow::Result;
-use crate::engine::renderer::{Rect, Renderer, Point};
 
 #[derive(Clone)]
 pub struct BoundingBox {
-    boxes: Vec<Rect>
+    boxes: Vec<Rect>,
 }
 
 impl BoundingBox {
     pub fn new(rects: Vec<Rect>) -> Self {
-        Self {
-            boxes: rects
-        }
+        Self { boxes: rects }
     }
 
     pub fn add(&mut self, rect: Rect) {
         self.boxes.push(rect);
     }
 
-    pub fn intersects<'a>(&'a self, other: &'a BoundingBox) -> Option<(&Rect, &Rect)> {
-        self.boxes.iter().find_map(|rect| {
-            other.boxes.iter().find_map(|other_rect| {
-                if rect.intersects(&other_rect) {
-                    Some((rect, other_rect))
-                } else {
-                    None
-                }
-            })
-        })
+    // 直前のフレームから displacement だけ移動してきた自分が other に触れた情報を、触れた時刻が早い順に返す
+    pub fn contacts(&self, displacement: Point, other: &BoundingBox) -> Vec<Contact> {
+        collision::contacts(&self.boxes, displacement, &other.boxes)
     }
 
     pub fn draw(&self, renderer: &Renderer) -> Result<()> {
@@ -36,8 +28,20 @@ impl BoundingBox {
         Ok(())
     }
 
+    pub fn rects(&self) -> &[Rect] {
+        &self.boxes
+    }
+
+    pub fn left(&self) -> i16 {
+        self.boxes.iter().map(|rect| rect.x()).min().unwrap_or(0)
+    }
+
     pub fn right(&self) -> i16 {
-        self.boxes.iter().map(|rect| rect.right()).max().unwrap_or(0)
+        self.boxes
+            .iter()
+            .map(|rect| rect.right())
+            .max()
+            .unwrap_or(0)
     }
 
     pub fn move_by(&mut self, position: Point) {
@@ -45,4 +49,4 @@ impl BoundingBox {
             rect.move_by(position);
         });
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,61 @@
+use std::f32::consts::TAU;
+
+use crate::engine::renderer::Point;
+
+// 障害物自身の動き方
+#[derive(Clone, Copy)]
+pub enum Motion {
+    // 動かない
+    Static,
+    // 初期位置を中心に、amplitude だけ離れた位置の間を period フレームで 1 往復する
+    Oscillate { amplitude: Point, period: u16 },
+}
+
+// 動き方と現在の進み具合を保持する構造体
+#[derive(Clone)]
+pub struct MotionPath {
+    motion: Motion,
+    frame: u16,
+    // 初期位置からのずれ
+    offset: Point,
+    // 直前のフレームでの移動量
+    delta: Point,
+}
+
+impl MotionPath {
+    pub fn new(motion: Motion) -> Self {
+        Self {
+            motion,
+            frame: 0,
+            offset: Point { x: 0, y: 0 },
+            delta: Point { x: 0, y: 0 },
+        }
+    }
+
+    // 1 フレーム分動きを進めて、その間の移動量を返す
+    pub fn update(&mut self) -> Point {
+        let next_offset = match self.motion {
+            Motion::Static => Point { x: 0, y: 0 },
+            Motion::Oscillate { amplitude, period } => {
+                self.frame = (self.frame + 1) % period.max(1);
+                let phase = (self.frame as f32 / period.max(1) as f32 * TAU).sin();
+                Point {
+                    x: (amplitude.x as f32 * phase).round() as i16,
+                    y: (amplitude.y as f32 * phase).round() as i16,
+                }
+            }
+        };
+
+        self.delta = Point {
+            x: next_offset.x - self.offset.x,
+            y: next_offset.y - self.offset.y,
+        };
+        self.offset = next_offset;
+
+        self.delta
+    }
+
+    pub fn delta(&self) -> Point {
+        self.delta
+    }
+}
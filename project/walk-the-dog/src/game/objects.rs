@@ -1,18 +1,183 @@
+pub mod enemy;
+pub mod item;
 pub mod platform;
 pub mod stone;
 
 use anyhow::Result;
 
-use crate::engine::renderer::Renderer;
+#[cfg(feature = "pixel_collision")]
+use std::rc::Rc;
 
-use super::{bounding_box::BoundingBox, rhb::RedHatBoy};
+#[cfg(feature = "pixel_collision")]
+use crate::engine::renderer::mask::{self, AlphaMask};
+use crate::engine::{
+    collision::{Contact, Side},
+    renderer::{Point, Rect, Renderer},
+};
+
+use super::{bounding_box::BoundingBox, power_up::PowerUp, rhb::RedHatBoy};
 
 pub trait GameObject {
     fn bounding_box(&self) -> BoundingBox;
     fn draw(&self, renderer: &Renderer) -> Result<()>;
+    // ピクセル単位の当たり判定に使うマスクと、マスクの左上の位置（マスクを持たない場合は None）
+    #[cfg(feature = "pixel_collision")]
+    fn alpha_mask(&self) -> Option<(Rc<AlphaMask>, Point)> {
+        None
+    }
+}
+
+// bounding box が region で重なっている 2 つのオブジェクトが、ピクセル単位でも重なっているかどうか
+// どちらかがマスクを持たない場合は、bounding box の判定をそのまま使う
+#[cfg(feature = "pixel_collision")]
+pub fn pixels_overlap<A, B>(a: &A, b: &B, region: &Rect) -> bool
+where
+    A: GameObject + ?Sized,
+    B: GameObject + ?Sized,
+{
+    match (a.alpha_mask(), b.alpha_mask()) {
+        (Some((a_mask, a_origin)), Some((b_mask, b_origin))) => {
+            mask::overlaps(&a_mask, a_origin, &b_mask, b_origin, region)
+        }
+        _ => true,
+    }
+}
+
+// pixel_collision が無効な場合は、bounding box の判定をそのまま使う
+#[cfg(not(feature = "pixel_collision"))]
+pub fn pixels_overlap<A, B>(_a: &A, _b: &B, _region: &Rect) -> bool
+where
+    A: GameObject + ?Sized,
+    B: GameObject + ?Sized,
+{
+    true
+}
+
+// rhb と衝突したときに起きること
+pub enum Collision {
+    // rhb がダメージを受ける
+    Harm,
+    // rhb が y の位置に着地する（carry は動く足場に運ばれる横方向の移動量）
+    LandOn { y: i16, carry: i16 },
+    // rhb がアイテムを獲得する
+    Collect(Reward),
+    // rhb が敵を上から踏みつける
+    Stomp,
+    // rhb を障害物の外に押し出す
+    PushOut(Point),
+    // rhb が頭上の障害物にぶつかる
+    Bonk,
+}
+
+// rhb が触れたときに障害物がどう振る舞うか
+#[derive(Clone, Copy)]
+pub enum CollisionBehaviour {
+    // 上に乗ることができ、下からぶつかると頭を打って落ち、横からぶつかると押し戻される
+    Solid,
+    // 上から落ちてきた場合だけ乗ることができ、それ以外の面からは通り抜けられる
+    OneWay,
+    // どの面から触れてもダメージを受ける
+    Hazard,
+    // 上から落ちてきた場合は踏みつけられ、それ以外の面から触れるとダメージを受ける
+    Stompable,
+    // どの面から触れても何も起きない（倒された敵など）
+    Intangible,
+    // 触れると reward を獲得する
+    Trigger(Reward),
+}
+
+impl CollisionBehaviour {
+    // contact に対して起きることを返す（carry は障害物が rhb を運ぶ横方向の移動量）
+    pub fn respond(&self, contact: &Contact, carry: i16) -> Vec<Collision> {
+        let land_on = Collision::LandOn {
+            y: contact.surface.y(),
+            carry,
+        };
+        match (self, contact.side) {
+            (CollisionBehaviour::Solid, Side::Top) => vec![land_on],
+            (CollisionBehaviour::Solid, Side::Bottom) => {
+                vec![Collision::PushOut(contact.push_out()), Collision::Bonk]
+            }
+            (CollisionBehaviour::Solid, _) => vec![Collision::PushOut(contact.push_out())],
+            // 下から通り抜けている途中で上面に近づいても、乗ったことにはしない
+            (CollisionBehaviour::OneWay, Side::Top) if !contact.initially_overlapping => {
+                vec![land_on]
+            }
+            (CollisionBehaviour::OneWay, _) => vec![],
+            (CollisionBehaviour::Hazard, _) => vec![Collision::Harm],
+            (CollisionBehaviour::Stompable, Side::Top) => vec![Collision::Stomp],
+            (CollisionBehaviour::Stompable, _) => vec![Collision::Harm],
+            (CollisionBehaviour::Intangible, _) => vec![],
+            (CollisionBehaviour::Trigger(reward), _) => vec![Collision::Collect(*reward)],
+        }
+    }
+}
+
+// object に触れた rhb の接触のうち、最も早いものに behaviour で応答する
+// displacement は object から見た rhb の移動量
+// 現在も重なっている場合は、ピクセル単位でも重なっているときだけ応答する
+// （移動の途中で触れて、現在は重なっていない場合はそのまま触れたとみなす）
+pub fn respond_to_rhb<T: GameObject + ?Sized>(
+    object: &T,
+    rhb: &RedHatBoy,
+    displacement: Point,
+    behaviour: CollisionBehaviour,
+    carry: i16,
+) -> Vec<Collision> {
+    let contacts = rhb
+        .bounding_box()
+        .contacts(displacement, &object.bounding_box());
+    match contacts.first() {
+        Some(contact)
+            if contact
+                .overlap
+                .as_ref()
+                .is_none_or(|region| pixels_overlap(rhb, object, region)) =>
+        {
+            behaviour.respond(contact, carry)
+        }
+        _ => vec![],
+    }
+}
+
+// アイテムを獲得したときの効果
+#[derive(Clone, Copy)]
+pub enum Reward {
+    Score(u32),
+    PowerUp(PowerUp),
 }
 
 pub trait Obstacle: GameObject {
-    fn update_position(&mut self, velocity: i16);
-    fn check_intersection(&self, rhb: &mut RedHatBoy);
+    // ワールドの原点を移したときに、x 方向に dx だけ移動させる
+    fn shift(&mut self, dx: i16);
+    // 障害物自身の動きを 1 フレーム分進める（動かない障害物は何もしない）
+    fn update_motion(&mut self) {}
+    // 直前の update_motion で障害物自身が動いた量
+    fn motion_delta(&self) -> Point {
+        Point { x: 0, y: 0 }
+    }
+    fn behaviour(&self) -> CollisionBehaviour;
+    // 障害物自身も動いているので、障害物から見た rhb の移動量で判定する
+    // 動く障害物の上に乗っている場合は、障害物と一緒に横方向に運ばれる
+    fn check_intersection(&self, rhb: &RedHatBoy) -> Vec<Collision> {
+        let delta = self.motion_delta();
+        respond_to_rhb(
+            self,
+            rhb,
+            rhb.displacement_from(delta),
+            self.behaviour(),
+            delta.x,
+        )
+    }
+}
+
+// rhb が触れると獲得できるアイテム
+pub trait Collectible: GameObject {
+    // ワールドの原点を移したときに、x 方向に dx だけ移動させる
+    fn shift(&mut self, dx: i16);
+    // アニメーションを 1 フレーム分進める
+    fn update(&mut self);
+    // target が近くにあれば、target に向かって引き寄せられる
+    fn attract_to(&mut self, target: Point);
+    fn check_intersection(&self, rhb: &RedHatBoy) -> Vec<Collision>;
 }
@@ -0,0 +1,323 @@
+use std::rc::Rc;
+
+use anyhow::Result;
+use gloo_utils::format::JsValueSerdeExt;
+
+use crate::{
+    browser,
+    engine::renderer::{
+        image,
+        sprite::{Cell, Sprite, SpriteSheet},
+        Point, Rect, Renderer,
+    },
+    game::{
+        bounding_box::BoundingBox,
+        motion::{Motion, MotionPath},
+    },
+};
+
+#[cfg(feature = "pixel_collision")]
+use crate::engine::renderer::mask::AlphaMask;
+
+use super::{CollisionBehaviour, GameObject, Obstacle};
+
+// 1 つのセルを表示し続けるフレーム数
+const FRAMES_PER_CELL: u8 = 6;
+const CELL_COUNT: u8 = 4;
+
+// 巡回・浮遊で 1 往復するフレーム数
+const PATROL_PERIOD: u16 = 180;
+const HOVER_PERIOD: u16 = 90;
+// 浮遊するときの上下の振れ幅
+const HOVER_HEIGHT: i16 = 16;
+// rhb を追いかけるときに 1 フレームで進む距離
+const CHASE_SPEED: f32 = 2.;
+
+// 倒された後に画面に残るフレーム数と、その間に落ちていく速さ
+const DEFEATED_FRAMES: u8 = 30;
+const DEFEATED_FALL_SPEED: i16 = 6;
+
+// 敵の種類
+#[derive(Clone, Copy)]
+pub enum EnemyKind {
+    // 地面を歩く敵
+    Slime,
+    // 空を飛ぶ敵
+    Bat,
+}
+
+impl EnemyKind {
+    fn frame_name(&self) -> &str {
+        match self {
+            EnemyKind::Slime => "Slime",
+            EnemyKind::Bat => "Bat",
+        }
+    }
+
+    // 倒された後に落ちていくかどうか（地面の上の敵はその場で潰れる）
+    fn falls_when_defeated(&self) -> bool {
+        match self {
+            EnemyKind::Slime => false,
+            EnemyKind::Bat => true,
+        }
+    }
+}
+
+// 敵の動き方
+#[derive(Clone, Copy)]
+pub enum Behaviour {
+    // 初期位置を中心に、左右に range だけ離れた位置の間を往復する
+    Patrol { range: i16 },
+    // 初期位置で上下に浮遊し、rhb が sight 以内に近づくと追いかける
+    Homing { sight: i16 },
+}
+
+impl Behaviour {
+    // 追いかけていないときの動き
+    fn wandering_motion(&self) -> Motion {
+        match self {
+            Behaviour::Patrol { range } => Motion::Oscillate {
+                amplitude: Point { x: *range, y: 0 },
+                period: PATROL_PERIOD,
+            },
+            Behaviour::Homing { .. } => Motion::Oscillate {
+                amplitude: Point {
+                    x: 0,
+                    y: HOVER_HEIGHT,
+                },
+                period: HOVER_PERIOD,
+            },
+        }
+    }
+
+    // target を見つけて追いかけ始めるかどうか
+    fn spots(&self, distance: f32) -> bool {
+        match self {
+            Behaviour::Patrol { .. } => false,
+            Behaviour::Homing { sight } => distance < *sight as f32,
+        }
+    }
+}
+
+// 敵の状態
+#[derive(Clone, Copy)]
+enum EnemyState {
+    // 決まった動きを繰り返している
+    Wandering,
+    // rhb を追いかけている
+    Chasing,
+    // rhb に踏まれて倒された（frames は倒されてからのフレーム数）
+    Defeated { frames: u8 },
+}
+
+// 敵の状態遷移を引き起こすイベント
+enum EnemyEvent {
+    // 1 フレーム分動く（target は rhb の位置）
+    Update { target: Point },
+    // rhb に上から踏まれた
+    Stomp,
+}
+
+pub struct Enemy {
+    sprite: Rc<Sprite>,
+    kind: EnemyKind,
+    behaviour: Behaviour,
+    position: Point,
+    motion: MotionPath,
+    state: EnemyState,
+    frame: u8,
+    // 直前の update で敵自身が動いた量
+    last_move: Point,
+}
+
+impl Enemy {
+    pub fn new(sprite: Rc<Sprite>, kind: EnemyKind, behaviour: Behaviour, position: Point) -> Self {
+        Self {
+            sprite,
+            kind,
+            behaviour,
+            position,
+            motion: MotionPath::new(behaviour.wandering_motion()),
+            state: EnemyState::Wandering,
+            frame: 0,
+            last_move: Point { x: 0, y: 0 },
+        }
+    }
+
+    pub async fn load_sprite() -> Result<Rc<Sprite>> {
+        let json = browser::fetch_json("enemies.json").await?;
+        let sprite_sheet: SpriteSheet = json.into_serde()?;
+        let image = image::load_image("enemies.png").await?;
+        let sprite = Rc::new(Sprite::new(sprite_sheet, image));
+
+        Ok(sprite)
+    }
+
+    // target に向かって追いかけるなど、敵自身の動きを 1 フレーム分進める
+    pub fn update(&mut self, target: Point) {
+        let previous = self.position;
+        self.transition(EnemyEvent::Update { target });
+        self.last_move = Point {
+            x: self.position.x - previous.x,
+            y: self.position.y - previous.y,
+        };
+    }
+
+    // rhb に上から踏まれて倒される
+    pub fn defeat(&mut self) {
+        self.transition(EnemyEvent::Stomp);
+    }
+
+    pub fn is_defeated(&self) -> bool {
+        matches!(self.state, EnemyState::Defeated { .. })
+    }
+
+    // 倒された後の演出が終わり、画面から取り除いてよいかどうか
+    pub fn is_gone(&self) -> bool {
+        matches!(self.state, EnemyState::Defeated { frames } if frames >= DEFEATED_FRAMES)
+    }
+
+    // イベントを受け取って状態遷移を行うメソッド
+    fn transition(&mut self, event: EnemyEvent) {
+        self.state = match (self.state, event) {
+            (EnemyState::Defeated { frames }, EnemyEvent::Update { .. }) => {
+                if self.kind.falls_when_defeated() {
+                    self.position.y += DEFEATED_FALL_SPEED;
+                }
+                EnemyState::Defeated {
+                    frames: frames.saturating_add(1),
+                }
+            }
+            (EnemyState::Defeated { frames }, EnemyEvent::Stomp) => EnemyState::Defeated { frames },
+            (_, EnemyEvent::Stomp) => {
+                self.frame = 0;
+                EnemyState::Defeated { frames: 0 }
+            }
+            (EnemyState::Wandering, EnemyEvent::Update { target }) => {
+                self.update_frame();
+                let delta = self.motion.update();
+                self.position.move_by(delta);
+                if self.behaviour.spots(self.distance_to(target)) {
+                    EnemyState::Chasing
+                } else {
+                    EnemyState::Wandering
+                }
+            }
+            (EnemyState::Chasing, EnemyEvent::Update { target }) => {
+                self.update_frame();
+                self.chase(target);
+                if self.behaviour.spots(self.distance_to(target)) {
+                    EnemyState::Chasing
+                } else {
+                    EnemyState::Wandering
+                }
+            }
+        };
+    }
+
+    fn update_frame(&mut self) {
+        self.frame = (self.frame + 1) % (CELL_COUNT * FRAMES_PER_CELL);
+    }
+
+    fn center(&self) -> Point {
+        let cell = self.current_cell();
+        Point {
+            x: self.position.x + cell.width() / 2,
+            y: self.position.y + cell.height() / 2,
+        }
+    }
+
+    fn distance_to(&self, target: Point) -> f32 {
+        let center = self.center();
+        ((target.x - center.x) as f32).hypot((target.y - center.y) as f32)
+    }
+
+    // target に向かって CHASE_SPEED だけ進む
+    fn chase(&mut self, target: Point) {
+        let center = self.center();
+        let dx = (target.x - center.x) as f32;
+        let dy = (target.y - center.y) as f32;
+        let distance = dx.hypot(dy);
+
+        if distance > 0. {
+            let step = CHASE_SPEED.min(distance);
+            self.position.move_by(Point {
+                x: (dx / distance * step).round() as i16,
+                y: (dy / distance * step).round() as i16,
+            });
+        }
+    }
+
+    fn current_cell(&self) -> &Cell {
+        let frame_name = match self.state {
+            EnemyState::Defeated { .. } => format!("{} Dead.png", self.kind.frame_name()),
+            _ => format!(
+                "{} ({}).png",
+                self.kind.frame_name(),
+                self.frame / FRAMES_PER_CELL + 1
+            ),
+        };
+        self.sprite.cell(&frame_name).expect("Cell not found")
+    }
+}
+
+impl GameObject for Enemy {
+    fn bounding_box(&self) -> BoundingBox {
+        BoundingBox::new(
+            self.current_cell()
+                .hitboxes_on_canvas(self.position.x, self.position.y),
+        )
+    }
+
+    fn draw(&self, renderer: &Renderer) -> Result<()> {
+        let cell = self.current_cell();
+        self.sprite.draw(
+            renderer,
+            &Rect::new_from_x_y(cell.x(), cell.y(), cell.width(), cell.height()),
+            &cell.to_rect_on_canvas(
+                self.position.x,
+                self.position.y,
+                cell.width(),
+                cell.height(),
+            ),
+        )?;
+
+        #[cfg(feature = "collision_debug")]
+        self.bounding_box().draw(renderer)?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "pixel_collision")]
+    fn alpha_mask(&self) -> Option<(std::rc::Rc<AlphaMask>, Point)> {
+        let cell = self.current_cell();
+        let origin = cell.to_rect_on_canvas(self.position.x, self.position.y, 0, 0);
+        Some((
+            self.sprite.mask(cell)?,
+            Point {
+                x: origin.x(),
+                y: origin.y(),
+            },
+        ))
+    }
+}
+
+// 敵は自分で動くので、当たり判定は直前の update で動いた分を差し引いて行う
+impl Obstacle for Enemy {
+    fn shift(&mut self, dx: i16) {
+        self.position.x += dx;
+    }
+
+    fn motion_delta(&self) -> Point {
+        self.last_move
+    }
+
+    // 踏まれたかどうかの判定は Platform に着地する場合と同じく、触れた面の向きで決める
+    fn behaviour(&self) -> CollisionBehaviour {
+        if self.is_defeated() {
+            CollisionBehaviour::Intangible
+        } else {
+            CollisionBehaviour::Stompable
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::rc::Rc;
+
+use anyhow::Result;
+use gloo_utils::format::JsValueSerdeExt;
+
+use crate::{
+    browser,
+    engine::renderer::{
+        image,
+        sprite::{Cell, Sprite, SpriteSheet},
+        Point, Rect, Renderer,
+    },
+    game::{
+        bounding_box::BoundingBox,
+        power_up::{PowerUp, MAGNET_RADIUS, MAGNET_SPEED},
+        rhb::RedHatBoy,
+    },
+};
+
+#[cfg(feature = "pixel_collision")]
+use crate::engine::renderer::mask::AlphaMask;
+
+use super::{respond_to_rhb, Collectible, Collision, CollisionBehaviour, GameObject, Reward};
+
+// 1 つのセルを表示し続けるフレーム数
+const FRAMES_PER_CELL: u8 = 3;
+
+// アイテムの種類
+#[derive(Clone, Copy)]
+pub enum ItemKind {
+    Coin,
+    Gem,
+    PowerUp(PowerUp),
+}
+
+impl ItemKind {
+    fn frame_name(&self) -> &str {
+        match self {
+            ItemKind::Coin => "Coin",
+            ItemKind::Gem => "Gem",
+            ItemKind::PowerUp(PowerUp::Shield) => "Shield",
+            ItemKind::PowerUp(PowerUp::Magnet) => "Magnet",
+            ItemKind::PowerUp(PowerUp::DoubleJump) => "DoubleJump",
+            ItemKind::PowerUp(PowerUp::SlowMotion) => "SlowMotion",
+        }
+    }
+
+    fn cell_count(&self) -> u8 {
+        match self {
+            ItemKind::Coin => 8,
+            ItemKind::Gem => 6,
+            ItemKind::PowerUp(_) => 4,
+        }
+    }
+
+    fn reward(&self) -> Reward {
+        match self {
+            ItemKind::Coin => Reward::Score(10),
+            ItemKind::Gem => Reward::Score(50),
+            ItemKind::PowerUp(power_up) => Reward::PowerUp(*power_up),
+        }
+    }
+}
+
+pub struct Item {
+    sprite: Rc<Sprite>,
+    kind: ItemKind,
+    position: Point,
+    frame: u8,
+}
+
+impl Item {
+    pub fn new(sprite: Rc<Sprite>, kind: ItemKind, position: Point) -> Self {
+        Self {
+            sprite,
+            kind,
+            position,
+            frame: 0,
+        }
+    }
+
+    pub async fn load_sprite() -> Result<Rc<Sprite>> {
+        let json = browser::fetch_json("items.json").await?;
+        let sprite_sheet: SpriteSheet = json.into_serde()?;
+        let image = image::load_image("items.png").await?;
+        let sprite = Rc::new(Sprite::new(sprite_sheet, image));
+
+        Ok(sprite)
+    }
+
+    fn current_cell(&self) -> &Cell {
+        let frame_name = format!(
+            "{} ({}).png",
+            self.kind.frame_name(),
+            self.frame / FRAMES_PER_CELL + 1
+        );
+        self.sprite.cell(&frame_name).expect("Cell not found")
+    }
+}
+
+impl GameObject for Item {
+    fn bounding_box(&self) -> BoundingBox {
+        BoundingBox::new(
+            self.current_cell()
+                .hitboxes_on_canvas(self.position.x, self.position.y),
+        )
+    }
+
+    fn draw(&self, renderer: &Renderer) -> Result<()> {
+        let cell = self.current_cell();
+        self.sprite.draw(
+            renderer,
+            &Rect::new_from_x_y(cell.x(), cell.y(), cell.width(), cell.height()),
+            &cell.to_rect_on_canvas(
+                self.position.x,
+                self.position.y,
+                cell.width(),
+                cell.height(),
+            ),
+        )?;
+
+        #[cfg(feature = "collision_debug")]
+        self.bounding_box().draw(renderer)?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "pixel_collision")]
+    fn alpha_mask(&self) -> Option<(std::rc::Rc<AlphaMask>, Point)> {
+        let cell = self.current_cell();
+        let origin = cell.to_rect_on_canvas(self.position.x, self.position.y, 0, 0);
+        Some((
+            self.sprite.mask(cell)?,
+            Point {
+                x: origin.x(),
+                y: origin.y(),
+            },
+        ))
+    }
+}
+
+impl Collectible for Item {
+    fn shift(&mut self, dx: i16) {
+        self.position.x += dx;
+    }
+
+    fn update(&mut self) {
+        self.frame = (self.frame + 1) % (self.kind.cell_count() * FRAMES_PER_CELL);
+    }
+
+    fn attract_to(&mut self, target: Point) {
+        let cell = self.current_cell();
+        let center = Point {
+            x: self.position.x + cell.width() / 2,
+            y: self.position.y + cell.height() / 2,
+        };
+        let dx = (target.x - center.x) as f32;
+        let dy = (target.y - center.y) as f32;
+        let distance = dx.hypot(dy);
+
+        if distance > 0. && distance < MAGNET_RADIUS as f32 {
+            // 目標を通り過ぎないように、1 フレームの移動量は残りの距離までに抑える
+            let step = (MAGNET_SPEED as f32).min(distance);
+            self.position.move_by(Point {
+                x: (dx / distance * step).round() as i16,
+                y: (dy / distance * step).round() as i16,
+            });
+        }
+    }
+
+    fn check_intersection(&self, rhb: &RedHatBoy) -> Vec<Collision> {
+        respond_to_rhb(
+            self,
+            rhb,
+            rhb.displacement(),
+            CollisionBehaviour::Trigger(self.kind.reward()),
+            0,
+        )
+    }
+}
@@ -10,39 +10,60 @@ use crate::{
         sprite::{Cell, Sprite, SpriteSheet},
         Point, Rect, Renderer,
     },
-    game::bounding_box::BoundingBox,
+    game::{
+        bounding_box::BoundingBox,
+        motion::{Motion, MotionPath},
+    },
 };
 
-use super::{GameObject, Obstacle};
+use super::{CollisionBehaviour, GameObject, Obstacle};
 
 pub struct Platform {
     sprite: Rc<Sprite>,
     position: Point,
     sprite_cells: Vec<Cell>,
     bounding_box: BoundingBox,
+    motion: MotionPath,
+    behaviour: CollisionBehaviour,
 }
 
 impl Platform {
-    pub fn new(
-        sprite: Rc<Sprite>,
-        position: Point,
-        sprite_names: &[&str],
-        mut bounding_box: BoundingBox,
-    ) -> Self {
-        let sprite_cells = sprite_names
+    pub fn new(sprite: Rc<Sprite>, position: Point, sprite_names: &[&str]) -> Self {
+        let sprite_cells: Vec<Cell> = sprite_names
             .iter()
             .map(|name| sprite.cell(name).cloned().expect("Error: Cell not found"))
             .collect();
-        bounding_box.move_by(position);
+
+        // 並べたセルそれぞれの当たり判定をまとめて platform の bounding box とする
+        let mut bounding_box = BoundingBox::new(vec![]);
+        let mut offset = 0;
+        for cell in &sprite_cells {
+            cell.hitboxes_on_canvas(position.x + offset, position.y)
+                .into_iter()
+                .for_each(|rect| bounding_box.add(rect));
+            offset += cell.width();
+        }
 
         Self {
             sprite,
             position,
             sprite_cells,
             bounding_box,
+            motion: MotionPath::new(Motion::Static),
+            behaviour: CollisionBehaviour::Solid,
         }
     }
 
+    pub fn with_motion(mut self, motion: Motion) -> Self {
+        self.motion = MotionPath::new(motion);
+        self
+    }
+
+    pub fn with_behaviour(mut self, behaviour: CollisionBehaviour) -> Self {
+        self.behaviour = behaviour;
+        self
+    }
+
     pub async fn load_sprite() -> Result<Rc<Sprite>> {
         let json = browser::fetch_json("tiles.json").await?;
         let sprite_sheet: SpriteSheet = json.into_serde()?;
@@ -83,21 +104,22 @@ impl GameObject for Platform {
 }
 
 impl Obstacle for Platform {
-    fn update_position(&mut self, velocity: i16) {
-        self.position.x += velocity;
-        self.bounding_box.move_by(Point { x: velocity, y: 0 });
+    fn shift(&mut self, dx: i16) {
+        self.position.x += dx;
+        self.bounding_box.move_by(Point { x: dx, y: 0 });
     }
 
-    fn check_intersection(&self, rhb: &mut crate::game::rhb::RedHatBoy) {
-        if let Some((rhb_rect, platform_rect)) = rhb.bounding_box().intersects(&self.bounding_box())
-        {
-            // rhb が platform より上にいるかどうかを判定
-            // かつ rhb が落下しているかどうかを判定
-            if rhb_rect.y() < platform_rect.y() && rhb.is_falling() {
-                rhb.land_on(platform_rect.y());
-            } else {
-                rhb.knock_out();
-            }
-        }
+    fn update_motion(&mut self) {
+        let delta = self.motion.update();
+        self.position.move_by(delta);
+        self.bounding_box.move_by(delta);
+    }
+
+    fn motion_delta(&self) -> Point {
+        self.motion.delta()
+    }
+
+    fn behaviour(&self) -> CollisionBehaviour {
+        self.behaviour
     }
 }
@@ -1,28 +1,40 @@
 use anyhow::Result;
-use web_sys::HtmlImageElement;
 
 use crate::{
     engine::renderer::{
         image::{self, Image},
-        Point, Rect, Renderer,
+        ImageElement, Point, Rect, Renderer,
+    },
+    game::{
+        bounding_box::BoundingBox,
+        motion::{Motion, MotionPath},
     },
-    game::bounding_box::BoundingBox,
 };
 
-use super::{GameObject, Obstacle};
+#[cfg(feature = "pixel_collision")]
+use crate::engine::renderer::mask::AlphaMask;
+
+use super::{CollisionBehaviour, GameObject, Obstacle};
 
 pub struct Stone {
     image: Image,
+    motion: MotionPath,
 }
 
 impl Stone {
-    pub fn new(image: HtmlImageElement, position: Point) -> Self {
+    pub fn new(image: ImageElement, position: Point) -> Self {
         Self {
             image: Image::new(image, position),
+            motion: MotionPath::new(Motion::Static),
         }
     }
 
-    pub async fn load_image() -> Result<HtmlImageElement> {
+    pub fn with_motion(mut self, motion: Motion) -> Self {
+        self.motion = MotionPath::new(motion);
+        self
+    }
+
+    pub async fn load_image() -> Result<ImageElement> {
         image::load_image("Stone.png").await
     }
 }
@@ -45,16 +57,29 @@ impl GameObject for Stone {
 
         Ok(())
     }
+
+    // 画像の透明度をマスクに使うので、丸い石の角の透明な部分に触れただけでは当たらない
+    #[cfg(feature = "pixel_collision")]
+    fn alpha_mask(&self) -> Option<(std::rc::Rc<AlphaMask>, Point)> {
+        Some((self.image.mask()?, *self.image.position()))
+    }
 }
 
 impl Obstacle for Stone {
-    fn update_position(&mut self, velocity: i16) {
-        self.image.move_horizontally(velocity);
+    fn shift(&mut self, dx: i16) {
+        self.image.move_horizontally(dx);
     }
 
-    fn check_intersection(&self, rhb: &mut crate::game::rhb::RedHatBoy) {
-        if let Some((_, _)) = rhb.bounding_box().intersects(&self.bounding_box()) {
-            rhb.knock_out();
-        }
+    fn update_motion(&mut self) {
+        let delta = self.motion.update();
+        self.image.move_by(delta);
+    }
+
+    fn motion_delta(&self) -> Point {
+        self.motion.delta()
+    }
+
+    fn behaviour(&self) -> CollisionBehaviour {
+        CollisionBehaviour::Hazard
     }
 }
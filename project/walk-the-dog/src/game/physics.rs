@@ -0,0 +1,314 @@
+use anyhow::{anyhow, Result};
+use gloo_utils::format::JsValueSerdeExt;
+use serde::Deserialize;
+use std::ops::RangeInclusive;
+
+use crate::browser;
+
+// rhb の動きを決める物理パラメータ
+// 速さの単位は 1 フレームあたりのピクセル数（y は下向きが正）
+// 位置は小数で計算するので、1 ピクセル未満の速さや加速度も指定できる
+// physics.json で一部の値だけを指定した場合、残りは既定値になる
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct PhysicsConfig {
+    pub running_speed: f32,
+    pub gravity: f32,
+    pub terminal_velocity: f32,
+    // ジャンプを始めたときの速さ
+    pub jump_speed: f32,
+    // ジャンプキーを早く離したときに、上昇の速さをこの値まで落とす（小さなジャンプになる）
+    pub jump_cut_speed: f32,
+    // 敵を踏みつけたときに跳ね上がる速さ
+    pub bounce_speed: f32,
+    // 足場から走り出た後も、ジャンプできるフレーム数（コヨーテタイム）
+    pub coyote_frames: u8,
+    // 着地する直前に押したジャンプを覚えておき、着地したときにジャンプするフレーム数
+    pub jump_buffer_frames: u8,
+    // 位置の y 座標から当たり判定の下端（足元）までの高さ
+    // スプライトシートの当たり判定と合っていないと地面に沈んだり浮いたりするので、physics.json では指定せず
+    // スプライトシートから求める（RedHatBoy::fit_physics）
+    #[serde(skip)]
+    pub player_height: i16,
+    pub frame_counts: FrameCounts,
+}
+
+// 各状態のアニメーションのフレーム数（スプライトシートの画像の枚数 × 3）
+// 1 以上で、スプライトシートにある画像の枚数 × 3 以下でなければならない（RedHatBoy::fit_physics で確かめる）
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct FrameCounts {
+    pub idle: u8,
+    pub running: u8,
+    pub sliding: u8,
+    pub jumping: u8,
+    pub falling: u8,
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        Self {
+            running_speed: 4.,
+            gravity: 1.,
+            terminal_velocity: 20.,
+            jump_speed: -25.,
+            jump_cut_speed: -8.,
+            bounce_speed: -15.,
+            coyote_frames: 6,
+            jump_buffer_frames: 8,
+            player_height: 121,
+            frame_counts: FrameCounts::default(),
+        }
+    }
+}
+
+impl Default for FrameCounts {
+    fn default() -> Self {
+        Self {
+            idle: 29,
+            running: 24,
+            sliding: 14,
+            jumping: 35,
+            falling: 29,
+        }
+    }
+}
+
+// 各パラメータが取れる値の範囲（physics.json の検証と、パネルでの調整の両方に使う）
+pub const RUNNING_SPEED: RangeInclusive<f32> = 0.0..=20.0;
+pub const GRAVITY: RangeInclusive<f32> = 0.05..=5.0;
+pub const TERMINAL_VELOCITY: RangeInclusive<f32> = 1.0..=60.0;
+pub const JUMP_SPEED: RangeInclusive<f32> = -60.0..=-1.0;
+pub const JUMP_CUT_SPEED: RangeInclusive<f32> = -60.0..=0.0;
+pub const BOUNCE_SPEED: RangeInclusive<f32> = -60.0..=-1.0;
+pub const ASSIST_FRAMES: RangeInclusive<f32> = 0.0..=60.0;
+
+impl PhysicsConfig {
+    pub async fn load() -> Result<Self> {
+        let json = browser::fetch_json("physics.json").await?;
+        let config: PhysicsConfig = json.into_serde()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    // 範囲外の値があれば、その名前と範囲をエラーにする
+    pub fn validate(&self) -> Result<()> {
+        check("running_speed", self.running_speed, RUNNING_SPEED)?;
+        check("gravity", self.gravity, GRAVITY)?;
+        check(
+            "terminal_velocity",
+            self.terminal_velocity,
+            TERMINAL_VELOCITY,
+        )?;
+        check("jump_speed", self.jump_speed, JUMP_SPEED)?;
+        check("jump_cut_speed", self.jump_cut_speed, JUMP_CUT_SPEED)?;
+        check("bounce_speed", self.bounce_speed, BOUNCE_SPEED)?;
+        check("coyote_frames", self.coyote_frames as f32, ASSIST_FRAMES)?;
+        check(
+            "jump_buffer_frames",
+            self.jump_buffer_frames as f32,
+            ASSIST_FRAMES,
+        )?;
+        self.frame_counts.validate()
+    }
+}
+
+impl FrameCounts {
+    // フレーム数が 0 だとアニメーションを進めるときに 0 で割ってしまう
+    fn validate(&self) -> Result<()> {
+        [
+            ("idle", self.idle),
+            ("running", self.running),
+            ("sliding", self.sliding),
+            ("jumping", self.jumping),
+            ("falling", self.falling),
+        ]
+        .iter()
+        .find(|(_, count)| *count == 0)
+        .map_or(Ok(()), |(name, _)| {
+            Err(anyhow!("physics: frame_counts.{} must be at least 1", name))
+        })
+    }
+}
+
+fn check(name: &str, value: f32, range: RangeInclusive<f32>) -> Result<()> {
+    if range.contains(&value) {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "physics: {} = {} is out of range {:?}",
+            name,
+            value,
+            range
+        ))
+    }
+}
+
+// プレイ中に物理パラメータを調整するためのデバッグ用パネル
+// P キーで表示を切り替え、W / S キーで項目を選び、A / D キーで値を増減する
+#[cfg(feature = "physics_debug")]
+pub mod panel {
+    use std::ops::RangeInclusive;
+
+    use super::{
+        PhysicsConfig, ASSIST_FRAMES, BOUNCE_SPEED, GRAVITY, JUMP_CUT_SPEED, JUMP_SPEED,
+        RUNNING_SPEED, TERMINAL_VELOCITY,
+    };
+    use crate::engine::{
+        key_state::KeyState,
+        renderer::{Point, Renderer},
+    };
+
+    const TOGGLE_KEY: &str = "KeyP";
+    const PREVIOUS_KEY: &str = "KeyW";
+    const NEXT_KEY: &str = "KeyS";
+    const DECREASE_KEY: &str = "KeyA";
+    const INCREASE_KEY: &str = "KeyD";
+    const KEYS: [&str; 5] = [
+        TOGGLE_KEY,
+        PREVIOUS_KEY,
+        NEXT_KEY,
+        DECREASE_KEY,
+        INCREASE_KEY,
+    ];
+
+    const PANEL_POSITION: Point = Point { x: 290, y: 40 };
+    const LINE_HEIGHT: i16 = 24;
+
+    // パネルで調整できる項目
+    // アニメーションのフレーム数はスプライトシートと合わなくなるので、physics.json でのみ変更できる
+    // （足元までの高さはスプライトシートから求めるので、調整できない）
+    struct Tunable {
+        name: &'static str,
+        step: f32,
+        // physics.json の検証と同じ範囲に収める
+        range: RangeInclusive<f32>,
+        get: fn(&PhysicsConfig) -> f32,
+        set: fn(&mut PhysicsConfig, f32),
+    }
+
+    const TUNABLES: [Tunable; 8] = [
+        Tunable {
+            name: "running_speed",
+            step: 0.25,
+            range: RUNNING_SPEED,
+            get: |config| config.running_speed,
+            set: |config, value| config.running_speed = value,
+        },
+        Tunable {
+            name: "gravity",
+            step: 0.05,
+            range: GRAVITY,
+            get: |config| config.gravity,
+            set: |config, value| config.gravity = value,
+        },
+        Tunable {
+            name: "terminal_velocity",
+            step: 1.,
+            range: TERMINAL_VELOCITY,
+            get: |config| config.terminal_velocity,
+            set: |config, value| config.terminal_velocity = value,
+        },
+        Tunable {
+            name: "jump_speed",
+            step: 0.5,
+            range: JUMP_SPEED,
+            get: |config| config.jump_speed,
+            set: |config, value| config.jump_speed = value,
+        },
+        Tunable {
+            name: "jump_cut_speed",
+            step: 0.5,
+            range: JUMP_CUT_SPEED,
+            get: |config| config.jump_cut_speed,
+            set: |config, value| config.jump_cut_speed = value,
+        },
+        Tunable {
+            name: "bounce_speed",
+            step: 0.5,
+            range: BOUNCE_SPEED,
+            get: |config| config.bounce_speed,
+            set: |config, value| config.bounce_speed = value,
+        },
+        Tunable {
+            name: "coyote_frames",
+            step: 1.,
+            range: ASSIST_FRAMES,
+            get: |config| config.coyote_frames as f32,
+            set: |config, value| config.coyote_frames = value as u8,
+        },
+        Tunable {
+            name: "jump_buffer_frames",
+            step: 1.,
+            range: ASSIST_FRAMES,
+            get: |config| config.jump_buffer_frames as f32,
+            set: |config, value| config.jump_buffer_frames = value as u8,
+        },
+    ];
+
+    #[derive(Default)]
+    pub struct PhysicsPanel {
+        visible: bool,
+        selected: usize,
+        // 直前のフレームで押されていたキー（押し続けても 1 回だけ反応させるため）
+        held: [bool; KEYS.len()],
+    }
+
+    impl PhysicsPanel {
+        // キー入力に応じて config を変更し、変更したかどうかを返す
+        pub fn update(&mut self, keystate: &KeyState, config: &mut PhysicsConfig) -> bool {
+            let mut pressed = [false; KEYS.len()];
+            KEYS.iter().enumerate().for_each(|(index, key)| {
+                let is_pressed = keystate.is_pressed(key);
+                pressed[index] = is_pressed && !self.held[index];
+                self.held[index] = is_pressed;
+            });
+            let [toggle, previous, next, decrease, increase] = pressed;
+
+            if toggle {
+                self.visible = !self.visible;
+            }
+            if !self.visible {
+                return false;
+            }
+
+            if previous {
+                self.selected = (self.selected + TUNABLES.len() - 1) % TUNABLES.len();
+            }
+            if next {
+                self.selected = (self.selected + 1) % TUNABLES.len();
+            }
+
+            let direction = match (decrease, increase) {
+                (true, false) => -1.,
+                (false, true) => 1.,
+                _ => return false,
+            };
+            let tunable = &TUNABLES[self.selected];
+            let value = (tunable.get)(config) + tunable.step * direction;
+            (tunable.set)(
+                config,
+                value.clamp(*tunable.range.start(), *tunable.range.end()),
+            );
+            true
+        }
+
+        pub fn draw(&self, renderer: &Renderer, config: &PhysicsConfig) {
+            if !self.visible {
+                return;
+            }
+
+            TUNABLES.iter().enumerate().for_each(|(line, tunable)| {
+                let cursor = if line == self.selected { ">" } else { " " };
+                renderer.draw_text(
+                    &format!("{} {} {}", cursor, tunable.name, (tunable.get)(config)),
+                    &Point {
+                        x: PANEL_POSITION.x,
+                        y: PANEL_POSITION.y + LINE_HEIGHT * line as i16,
+                    },
+                );
+            });
+        }
+    }
+}
@@ -0,0 +1,108 @@
+// 一定時間 rhb の挙動を変化させるパワーアップ
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PowerUp {
+    // knock out を 1 回だけ防ぐ
+    Shield,
+    // 近くのアイテムを引き寄せる
+    Magnet,
+    // ジャンプ中にもう一度だけジャンプできる
+    DoubleJump,
+    // ゲーム全体の進行を遅くする
+    SlowMotion,
+}
+
+// 磁石がアイテムを引き寄せる範囲と、1 フレームで引き寄せる距離
+pub const MAGNET_RADIUS: i16 = 200;
+pub const MAGNET_SPEED: i16 = 8;
+
+// スローモーション中のゲームの進行速度の倍率
+pub const SLOW_MOTION_SCALE: f32 = 0.5;
+
+// シールドで攻撃を防いだ後、続けて攻撃を受けないフレーム数
+const INVULNERABLE_FRAMES: u16 = 60;
+
+impl PowerUp {
+    pub const ALL: [PowerUp; 4] = [
+        PowerUp::Shield,
+        PowerUp::Magnet,
+        PowerUp::DoubleJump,
+        PowerUp::SlowMotion,
+    ];
+
+    // 効果が続くフレーム数（実時間の 1/60 秒を 1 フレームとする）
+    fn duration(&self) -> f32 {
+        match self {
+            PowerUp::Shield => 600.,
+            PowerUp::Magnet => 480.,
+            PowerUp::DoubleJump => 600.,
+            PowerUp::SlowMotion => 240.,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            PowerUp::Shield => "SHIELD",
+            PowerUp::Magnet => "MAGNET",
+            PowerUp::DoubleJump => "DOUBLE JUMP",
+            PowerUp::SlowMotion => "SLOW",
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            PowerUp::Shield => 0,
+            PowerUp::Magnet => 1,
+            PowerUp::DoubleJump => 2,
+            PowerUp::SlowMotion => 3,
+        }
+    }
+}
+
+// パワーアップごとの残りフレーム数を管理する
+// パワーアップの残り時間は実時間で数え、スローモーション中でも長くならないようにする
+// 無敵時間は障害物と重なっている間に再び攻撃を受けないためのものなので、ゲームの update の回数で数える
+#[derive(Clone, Default)]
+pub struct Modifiers {
+    remaining: [f32; 4],
+    invulnerable: u16,
+}
+
+impl Modifiers {
+    pub fn grant(&mut self, power_up: PowerUp) {
+        self.remaining[power_up.index()] = power_up.duration();
+    }
+
+    pub fn is_active(&self, power_up: PowerUp) -> bool {
+        self.remaining(power_up) > 0
+    }
+
+    // 残りの実時間のフレーム数（端数は切り上げる）
+    pub fn remaining(&self, power_up: PowerUp) -> u16 {
+        self.remaining[power_up.index()].ceil() as u16
+    }
+
+    // 実時間で frames フレーム分、パワーアップの残り時間を減らす
+    pub fn elapse(&mut self, frames: f32) {
+        self.remaining
+            .iter_mut()
+            .for_each(|remaining| *remaining = (*remaining - frames).max(0.));
+    }
+
+    // ゲームの update 1 回分、無敵時間を減らす
+    pub fn tick(&mut self) {
+        self.invulnerable = self.invulnerable.saturating_sub(1);
+    }
+
+    pub fn can_absorb_hit(&self) -> bool {
+        self.invulnerable > 0 || self.is_active(PowerUp::Shield)
+    }
+
+    // シールドを消費して攻撃を防ぐ
+    // 防いだ直後は、同じ障害物と重なっている間に再び攻撃を受けないようにしばらく無敵になる
+    pub fn absorb_hit(&mut self) {
+        if self.invulnerable == 0 {
+            self.remaining[PowerUp::Shield.index()] = 0.;
+            self.invulnerable = INVULNERABLE_FRAMES;
+        }
+    }
+}
@@ -1,28 +1,63 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use gloo_utils::format::JsValueSerdeExt;
+#[cfg(feature = "history_debug")]
+use std::cell::Ref;
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
 
 use crate::browser;
+#[cfg(feature = "pixel_collision")]
+use crate::engine::renderer::mask::AlphaMask;
 use crate::engine::renderer::sprite::{Cell, Sprite, SpriteSheet};
 use crate::engine::renderer::{image, Point, Rect, Renderer};
 
+use self::history::History;
 use self::red_hat_boy_states::*;
+use self::transitions::{EventKind, StateKind, Transition, TRANSITIONS};
 use super::bounding_box::BoundingBox;
 use super::objects::GameObject;
+use super::physics::PhysicsConfig;
+use super::power_up::PowerUp;
+
+mod history;
+#[cfg(test)]
+mod tests;
+mod transitions;
+
+pub use self::history::history_json;
+#[cfg(feature = "history_debug")]
+pub use self::history::overlay::HistoryOverlay;
+pub use self::transitions::{state_diagram, DiagramFormat};
 
 // 座標系関連
+// 地面はタイルの障害物として配置されるので、FLOOR は開始時の y 座標としてのみ用いる
 pub const STARTING_POINT: i16 = -20;
-pub const FLOOR: i16 = 479;
+pub const FLOOR: i16 = 415;
 
 pub struct RedHatBoy {
     state_machine: RedHatBoyStateMachine,
     sprite: Sprite,
+    // 直前の update を行う前の位置
+    previous_position: Point,
+    // ログに出した、遷移表にない状態とイベントの組（同じ組を何度もログに出さないため）
+    logged_unhandled: HashSet<(StateKind, EventKind)>,
+    // 受け取ったイベントと状態の変化の履歴
+    history: Rc<RefCell<History>>,
 }
 
 impl RedHatBoy {
-    pub fn new(sprite: Sprite, position: Point) -> Self {
+    pub fn new(sprite: Sprite, position: Point, physics: PhysicsConfig) -> Self {
+        // 遷移表の誤りは開発中に気付けるようにする
+        #[cfg(debug_assertions)]
+        transitions::validate().iter().for_each(|problem| {
+            log!("RedHatBoy state machine: {}", problem);
+        });
+
         Self {
-            state_machine: RedHatBoyStateMachine::Idle(RedHatBoyState::<Idle>::new(position)),
+            state_machine: RedHatBoyStateMachine::new(position, physics),
             sprite,
+            previous_position: position,
+            logged_unhandled: HashSet::new(),
+            history: History::shared(),
         }
     }
 
@@ -42,28 +77,52 @@ impl RedHatBoy {
 
         Ok(sprite)
     }
+
+    // physics をスプライトシートに合わせる
+    // 各アニメーションのフレーム数の分だけ画像があるかを確かめ、足元までの高さは立っている画像の当たり判定から求める
+    pub fn fit_physics(sprite: &Sprite, mut physics: PhysicsConfig) -> Result<PhysicsConfig> {
+        for (animation, frame_count) in animations(&physics.frame_counts) {
+            if let Some(missing) = (0..frame_count)
+                .map(|frame| cell_name(animation, frame))
+                .find(|name| sprite.cell(name).is_none())
+            {
+                return Err(anyhow!(
+                    "physics: {} frames of {} need {}, which is not in the sprite sheet",
+                    frame_count,
+                    animation,
+                    missing
+                ));
+            }
+        }
+
+        let standing = cell_name(IDLE_FRAME_NAME, 0);
+        physics.player_height = sprite
+            .cell(&standing)
+            .ok_or(anyhow!("{} is not in the sprite sheet", standing))?
+            .hitboxes_on_canvas(0, 0)
+            .iter()
+            .map(|hitbox| hitbox.bottom())
+            .max()
+            .unwrap_or(physics.player_height);
+
+        Ok(physics)
+    }
+}
+
+// アニメーションの frame フレーム目に描画する画像の名前（1 枚の画像を 3 フレームずつ表示する）
+fn cell_name(animation: &str, frame: u8) -> String {
+    format!("{} ({}).png", animation, frame / 3 + 1)
 }
 
 impl GameObject for RedHatBoy {
     fn bounding_box(&self) -> BoundingBox {
-        const X_OFFSET: i16 = 18;
-        const Y_OFFSET: i16 = 14;
-        const WIDTH_OFFSET: i16 = -28;
-        const HEIGHT_OFFSET: i16 = 0;
-
-        let sprite = self.current_sprite();
-        let mut raw_rect = sprite.to_rect_on_canvas(
-            self.state_machine.context().position.x,
-            self.state_machine.context().position.y,
-            sprite.width(),
-            sprite.height(),
-        );
-        raw_rect.set_x(raw_rect.x() + X_OFFSET);
-        raw_rect.set_y(raw_rect.y() + Y_OFFSET);
-        raw_rect.w += WIDTH_OFFSET;
-        raw_rect.h += HEIGHT_OFFSET;
-
-        BoundingBox::new(vec![raw_rect])
+        // 当たり判定はスプライトシートのフレームごとに定義されている
+        // （スライディング中は当たり判定が低くなり、頭上の障害物をくぐれる）
+        let position = self.position();
+        BoundingBox::new(
+            self.current_sprite()
+                .hitboxes_on_canvas(position.x, position.y),
+        )
     }
 
     fn draw(&self, renderer: &Renderer) -> Result<()> {
@@ -75,8 +134,8 @@ impl GameObject for RedHatBoy {
             &renderer,
             &&Rect::new_from_x_y(sprite.x(), sprite.y(), sprite.width(), sprite.height()),
             &sprite.to_rect_on_canvas(
-                self.state_machine.context().position.x,
-                self.state_machine.context().position.y,
+                self.position().x,
+                self.position().y,
                 sprite.width(),
                 sprite.height(),
             ),
@@ -88,14 +147,27 @@ impl GameObject for RedHatBoy {
 
         Ok(())
     }
+
+    #[cfg(feature = "pixel_collision")]
+    fn alpha_mask(&self) -> Option<(std::rc::Rc<AlphaMask>, Point)> {
+        let cell = self.current_sprite();
+        let position = self.position();
+        let origin = cell.to_rect_on_canvas(position.x, position.y, 0, 0);
+        Some((
+            self.sprite.mask(cell)?,
+            Point {
+                x: origin.x(),
+                y: origin.y(),
+            },
+        ))
+    }
 }
 
 impl RedHatBoy {
     fn frame_name(&self) -> String {
-        format!(
-            "{} ({}).png",
+        cell_name(
             self.state_machine.frame_name(),
-            self.state_machine.context().frame / 3 + 1
+            self.state_machine.context().frame,
         )
     }
 
@@ -106,40 +178,177 @@ impl RedHatBoy {
         self.sprite.cell(&frame_name).expect("Cell not found")
     }
 
-    pub fn is_falling(&self) -> bool {
-        self.state_machine.context().velocity.y > 0
+    // 描画や当たり判定に使う、ピクセル単位に丸めた位置
+    // y は切り上げるので、1 ピクセル未満でも足場に向かって落ちていれば足場に触れる
+    fn position(&self) -> Point {
+        let position = self.state_machine.context().position;
+        Point {
+            x: position.x.round() as i16,
+            y: position.y.ceil() as i16,
+        }
+    }
+
+    pub fn pos_y(&self) -> i16 {
+        self.position().y
+    }
+
+    // 直前の update からの、ワールドに対する移動量
+    pub fn displacement(&self) -> Point {
+        let position = self.position();
+        Point {
+            x: position.x - self.previous_position.x,
+            y: position.y - self.previous_position.y,
+        }
+    }
+
+    // 直前の update から delta だけ動いた物体から見た、rhb の移動量
+    pub fn displacement_from(&self, delta: Point) -> Point {
+        let displacement = self.displacement();
+        Point {
+            x: displacement.x - delta.x,
+            y: displacement.y - delta.y,
+        }
+    }
+
+    // 磁石がアイテムを引き寄せる目標の位置
+    pub fn center(&self) -> Point {
+        let bounding_box = self.bounding_box();
+        bounding_box
+            .rects()
+            .first()
+            .map(|rect| rect.center())
+            .unwrap_or(self.position())
+    }
+
+    // 倒されたかどうか（倒れていく途中も含む）
+    pub fn is_defeated(&self) -> bool {
+        matches!(
+            self.state_machine.kind(),
+            StateKind::Falling | StateKind::KnockedOut
+        )
+    }
+
+    pub fn has_power_up(&self, power_up: PowerUp) -> bool {
+        self.state_machine.context().modifiers.is_active(power_up)
+    }
+
+    // パワーアップの効果が残っているフレーム数（実時間）
+    pub fn power_up_remaining(&self, power_up: PowerUp) -> u16 {
+        self.state_machine.context().modifiers.remaining(power_up)
+    }
+
+    // パワーアップの残り時間を、実時間で frames フレーム分進める（状態や履歴は変えない）
+    // スローモーション中は update の回数が減るので、update 1 回あたり 1 / time_scale フレームずつ進める
+    pub fn elapse_power_ups(&mut self, frames: f32) {
+        self.state_machine = self
+            .state_machine
+            .map_context(|context| context.modifiers.elapse(frames));
+    }
+
+    // 遷移表に従って状態遷移を行い、履歴に記録する
+    // 遷移表にないイベントは無視して、状態とイベントの組ごとに 1 回だけそのことをログに出す
+    // （遷移表で無視すると決めているイベントはログに出さない）
+    fn transition(&mut self, event: Event) {
+        let from = self.state_machine.kind();
+        let to = match self.state_machine.transition(event) {
+            Some(next) => {
+                self.state_machine = next;
+                Some(self.state_machine.kind())
+            }
+            None => {
+                if !self.state_machine.ignores(event)
+                    && self.logged_unhandled.insert((from, event.kind()))
+                {
+                    log!("RedHatBoy ignored {:?} while {:?}", event.kind(), from);
+                }
+                None
+            }
+        };
+        self.history
+            .borrow_mut()
+            .record(event, from, to, self.state_machine.context());
+    }
+
+    #[cfg(feature = "history_debug")]
+    pub fn history(&self) -> Ref<'_, History> {
+        self.history.borrow()
     }
 
-    pub fn walking_speed(&self) -> i16 {
-        self.state_machine.context().velocity.x
+    // 状態はそのままで、物理パラメータだけを差し替える
+    #[cfg(feature = "physics_debug")]
+    pub fn set_physics(&mut self, physics: PhysicsConfig) {
+        self.state_machine = self
+            .state_machine
+            .map_context(|context| context.physics = physics);
     }
 
     pub fn update(&mut self) {
-        self.state_machine.transition(Event::Update);
+        self.previous_position = self.position();
+        self.transition(Event::Update);
+    }
+
+    // ワールドの原点を移したときに、x 方向に dx だけ移動させる（状態や履歴は変えない）
+    pub fn shift(&mut self, dx: i16) {
+        self.state_machine = self
+            .state_machine
+            .map_context(|context| context.position.x += dx as f32);
+        self.previous_position.x += dx;
     }
 
     pub fn run_right(&mut self) {
-        self.state_machine.transition(Event::RunRight);
+        self.transition(Event::RunRight);
     }
 
     pub fn run_left(&mut self) {
-        self.state_machine.transition(Event::RunLeft);
+        self.transition(Event::RunLeft);
     }
 
     pub fn slide(&mut self) {
-        self.state_machine.transition(Event::Slide);
+        self.transition(Event::Slide);
     }
 
     pub fn jump(&mut self) {
-        self.state_machine.transition(Event::Jump);
+        self.transition(Event::Jump);
+    }
+
+    pub fn release_jump(&mut self) {
+        self.transition(Event::ReleaseJump);
+    }
+
+    // 障害物からダメージを受ける（シールドがあれば防げる）
+    pub fn hit(&mut self) {
+        self.transition(Event::Hit);
     }
 
     pub fn knock_out(&mut self) {
-        self.state_machine.transition(Event::KnockOut);
+        self.transition(Event::KnockOut);
+    }
+
+    pub fn power_up(&mut self, power_up: PowerUp) {
+        self.transition(Event::PowerUp(power_up));
     }
 
     pub fn land_on(&mut self, y: i16) {
-        self.state_machine.transition(Event::Land(y));
+        self.transition(Event::Land(y));
+    }
+
+    pub fn carry(&mut self, dx: i16) {
+        self.transition(Event::Carry(dx));
+    }
+
+    // 障害物にめり込まないように offset だけ押し出される
+    pub fn push_out(&mut self, offset: Point) {
+        self.transition(Event::PushOut(offset));
+    }
+
+    // 頭上の障害物にぶつかり、上昇をやめる
+    pub fn bonk(&mut self) {
+        self.transition(Event::Bonk);
+    }
+
+    // 敵を踏みつけた反動で跳ね上がる
+    pub fn bounce(&mut self) {
+        self.transition(Event::Bounce);
     }
 }
 
@@ -149,17 +358,24 @@ enum RedHatBoyStateMachine {
     Running(RedHatBoyState<Running>),
     Sliding(RedHatBoyState<Sliding>),
     Jumping(RedHatBoyState<Jumping>),
+    Airborne(RedHatBoyState<Airborne>),
     Falling(RedHatBoyState<Falling>),
     KnockedOut(RedHatBoyState<KnockedOut>),
 }
 
 impl RedHatBoyStateMachine {
+    // スプライトを持たないので、ブラウザがなくても作って動かせる
+    fn new(position: Point, physics: PhysicsConfig) -> Self {
+        RedHatBoyStateMachine::Idle(RedHatBoyState::<Idle>::new(position, physics))
+    }
+
     fn frame_name(&self) -> &str {
         match self {
             RedHatBoyStateMachine::Idle(state) => state.frame_name(),
             RedHatBoyStateMachine::Running(state) => state.frame_name(),
             RedHatBoyStateMachine::Sliding(state) => state.frame_name(),
             RedHatBoyStateMachine::Jumping(state) => state.frame_name(),
+            RedHatBoyStateMachine::Airborne(state) => state.frame_name(),
             RedHatBoyStateMachine::Falling(state) => state.frame_name(),
             RedHatBoyStateMachine::KnockedOut(state) => state.frame_name(),
         }
@@ -171,66 +387,63 @@ impl RedHatBoyStateMachine {
             RedHatBoyStateMachine::Running(state) => &state.context(),
             RedHatBoyStateMachine::Sliding(state) => &state.context(),
             RedHatBoyStateMachine::Jumping(state) => &state.context(),
+            RedHatBoyStateMachine::Airborne(state) => state.context(),
             RedHatBoyStateMachine::Falling(state) => &state.context(),
             RedHatBoyStateMachine::KnockedOut(state) => &state.context(),
         }
     }
 
-    // イベントを受け取って状態遷移を行うメソッド
-    fn transition(&mut self, event: Event) {
-        match (&self, event) {
-            // キー入力による状態遷移
-            (RedHatBoyStateMachine::Idle(ref state), Event::RunRight) => {
-                *self = state.start_run().into()
-            }
-            (RedHatBoyStateMachine::Running(ref state), Event::RunLeft) => {
-                *self = state.run_left().into()
-            }
-            (RedHatBoyStateMachine::Running(ref state), Event::RunRight) => {
-                *self = state.run_right().into()
-            }
-            (RedHatBoyStateMachine::Running(ref state), Event::Slide) => {
-                *self = state.slide().into()
-            }
-            (RedHatBoyStateMachine::Running(ref state), Event::Jump) => *self = state.jump().into(),
-            // 衝突による状態遷移
-            (RedHatBoyStateMachine::Running(ref state), Event::KnockOut) => {
-                *self = state.knock_out().into()
-            }
-            (RedHatBoyStateMachine::Sliding(ref state), Event::KnockOut) => {
-                *self = state.knock_out().into()
-            }
-            (RedHatBoyStateMachine::Jumping(ref state), Event::KnockOut) => {
-                *self = state.knock_out().into()
-            }
-            // 踏み台のへの衝突による状態遷移
-            (RedHatBoyStateMachine::Jumping(ref state), Event::Land(y)) => {
-                *self = state.land_on(y).into()
-            }
-            (RedHatBoyStateMachine::Running(ref state), Event::Land(y)) => {
-                *self = state.land_on(y).into()
-            }
-            (RedHatBoyStateMachine::Sliding(ref state), Event::Land(y)) => {
-                *self = state.land_on(y).into()
-            }
-            // 時間経過による update 処理
-            (RedHatBoyStateMachine::Idle(ref state), Event::Update) => {
-                *self = state.update().into()
-            }
-            (RedHatBoyStateMachine::Running(ref state), Event::Update) => {
-                *self = state.update().into()
-            }
-            (RedHatBoyStateMachine::Sliding(ref state), Event::Update) => {
-                *self = state.update().into()
-            }
-            (RedHatBoyStateMachine::Jumping(ref state), Event::Update) => {
-                *self = state.update().into()
-            }
-            (RedHatBoyStateMachine::Falling(ref state), Event::Update) => {
-                *self = state.update().into()
-            }
-            _ => {}
-        };
+    // 状態はそのままで、context に f を適用する
+    fn map_context(&self, f: impl FnOnce(&mut RedHatBoyContext)) -> Self {
+        match self {
+            RedHatBoyStateMachine::Idle(state) => state.map_context(f).into(),
+            RedHatBoyStateMachine::Running(state) => state.map_context(f).into(),
+            RedHatBoyStateMachine::Sliding(state) => state.map_context(f).into(),
+            RedHatBoyStateMachine::Jumping(state) => state.map_context(f).into(),
+            RedHatBoyStateMachine::Airborne(state) => state.map_context(f).into(),
+            RedHatBoyStateMachine::Falling(state) => state.map_context(f).into(),
+            RedHatBoyStateMachine::KnockedOut(state) => state.map_context(f).into(),
+        }
+    }
+
+    fn kind(&self) -> StateKind {
+        match self {
+            RedHatBoyStateMachine::Idle(_) => StateKind::Idle,
+            RedHatBoyStateMachine::Running(_) => StateKind::Running,
+            RedHatBoyStateMachine::Sliding(_) => StateKind::Sliding,
+            RedHatBoyStateMachine::Jumping(_) => StateKind::Jumping,
+            RedHatBoyStateMachine::Airborne(_) => StateKind::Airborne,
+            RedHatBoyStateMachine::Falling(_) => StateKind::Falling,
+            RedHatBoyStateMachine::KnockedOut(_) => StateKind::KnockedOut,
+        }
+    }
+
+    // 遷移表の中から event に当てはまる最初の行で状態遷移を行う
+    // 当てはまる行がないか、無視する行に当てはまれば None を返す（イベントは無視される）
+    fn transition(&self, event: Event) -> Option<Self> {
+        let kind = self.kind();
+        let transition = self.find_transition(event)?;
+        let next = transition.apply(self, event)?;
+        debug_assert!(
+            transition.allows(kind, next.kind()),
+            "{:?} + {:?} led to {:?}, which is not in the transition table",
+            kind,
+            event.kind(),
+            next.kind()
+        );
+        Some(next)
+    }
+
+    // 遷移表でこの状態では無視すると決めているイベントかどうか
+    fn ignores(&self, event: Event) -> bool {
+        self.find_transition(event)
+            .is_some_and(|transition| transition.ignores())
+    }
+
+    fn find_transition(&self, event: Event) -> Option<&'static Transition> {
+        TRANSITIONS
+            .iter()
+            .find(|transition| transition.matches(self.kind(), event.kind(), self.context()))
     }
 }
 
@@ -259,26 +472,40 @@ impl From<RedHatBoyState<Jumping>> for RedHatBoyStateMachine {
     }
 }
 
+impl From<RedHatBoyState<Airborne>> for RedHatBoyStateMachine {
+    fn from(state: RedHatBoyState<Airborne>) -> Self {
+        RedHatBoyStateMachine::Airborne(state)
+    }
+}
+
 impl From<RedHatBoyState<Falling>> for RedHatBoyStateMachine {
     fn from(state: RedHatBoyState<Falling>) -> Self {
         RedHatBoyStateMachine::Falling(state)
     }
 }
 
-impl From<SlidngEndState> for RedHatBoyStateMachine {
-    fn from(state: SlidngEndState) -> Self {
+impl From<RedHatBoyState<KnockedOut>> for RedHatBoyStateMachine {
+    fn from(state: RedHatBoyState<KnockedOut>) -> Self {
+        RedHatBoyStateMachine::KnockedOut(state)
+    }
+}
+
+impl From<RunningEndState> for RedHatBoyStateMachine {
+    fn from(state: RunningEndState) -> Self {
         match state {
-            SlidngEndState::Sliding(state) => RedHatBoyStateMachine::Sliding(state),
-            SlidngEndState::Complete(state) => RedHatBoyStateMachine::Running(state),
+            RunningEndState::Running(state) => RedHatBoyStateMachine::Running(state),
+            RunningEndState::Jumping(state) => RedHatBoyStateMachine::Jumping(state),
+            RunningEndState::Airborne(state) => RedHatBoyStateMachine::Airborne(state),
         }
     }
 }
 
-impl From<JumpEndState> for RedHatBoyStateMachine {
-    fn from(state: JumpEndState) -> Self {
+impl From<SlidngEndState> for RedHatBoyStateMachine {
+    fn from(state: SlidngEndState) -> Self {
         match state {
-            JumpEndState::Jumping(state) => RedHatBoyStateMachine::Jumping(state),
-            JumpEndState::Complete(state) => RedHatBoyStateMachine::Running(state),
+            SlidngEndState::Sliding(state) => RedHatBoyStateMachine::Sliding(state),
+            SlidngEndState::Complete(state) => RedHatBoyStateMachine::Running(state),
+            SlidngEndState::Airborne(state) => RedHatBoyStateMachine::Airborne(state),
         }
     }
 }
@@ -293,41 +520,46 @@ impl From<FallingEndState> for RedHatBoyStateMachine {
 }
 
 // イベント
+#[derive(Clone, Copy, Debug)]
 enum Event {
     RunRight,
     RunLeft,
     Slide,
     Jump,
+    ReleaseJump,
     Update,
+    Hit,
     KnockOut,
     Land(i16),
+    Carry(i16),
+    Bounce,
+    Bonk,
+    PushOut(Point),
+    PowerUp(PowerUp),
 }
 
 mod red_hat_boy_states {
-    use crate::engine::renderer::Point;
-
-    // 座標系関連
-    use super::super::HEIGHT;
-    use super::FLOOR;
-    const PLAYER_HEIGHT: i16 = HEIGHT - FLOOR;
-    const RUNNING_SPEED: i16 = 4;
-    const JUMP_SPEED: i16 = -25;
-    const GRAVITY: i16 = 1;
-    const TERMINAL_VELOCITY: i16 = 20;
+    use crate::engine::renderer::{Point, Vector};
+    use crate::game::physics::{FrameCounts, PhysicsConfig};
+    use crate::game::power_up::{Modifiers, PowerUp};
 
     // フレーム名
-    const IDLE_FRAME_NAME: &str = "Idle";
+    pub(super) const IDLE_FRAME_NAME: &str = "Idle";
     const RUNNING_FRAME_NAME: &str = "Run";
     const SLIDING_FRAME_NAME: &str = "Slide";
     const JUMPING_FRAME_NAME: &str = "Jump";
     const FALLING_FRAME_NAME: &str = "Dead";
 
-    // フレーム数
-    const IDLE_FRAME_COUNT: u8 = 29;
-    const RUNNING_FRAME_COUNT: u8 = 24;
-    const SLIDING_FRAME_COUNT: u8 = 14;
-    const JUMPING_FRAME_COUNT: u8 = 35;
-    const FALLING_FRAME_COUNT: u8 = 29;
+    // アニメーションごとのフレーム名と、そのフレーム数
+    pub(super) fn animations(frame_counts: &FrameCounts) -> [(&'static str, u8); 5] {
+        [
+            (IDLE_FRAME_NAME, frame_counts.idle),
+            (RUNNING_FRAME_NAME, frame_counts.running),
+            (SLIDING_FRAME_NAME, frame_counts.sliding),
+            (JUMPING_FRAME_NAME, frame_counts.jumping),
+            (FALLING_FRAME_NAME, frame_counts.falling),
+        ]
+    }
 
     // RHB の状態を表す構造体
     pub(super) struct RedHatBoyState<S> {
@@ -341,7 +573,20 @@ mod red_hat_boy_states {
         }
     }
 
+    impl<S: Copy> RedHatBoyState<S> {
+        // 状態を変えずに context だけを更新する
+        pub(super) fn map_context(&self, f: impl FnOnce(&mut RedHatBoyContext)) -> Self {
+            let mut context = self.context.clone();
+            f(&mut context);
+            RedHatBoyState {
+                context,
+                _state: self._state,
+            }
+        }
+    }
+
     // 状態を表す型
+    #[derive(Clone, Copy)]
     pub(super) struct Idle;
     impl RedHatBoyState<Idle> {
         pub(super) fn frame_name(&self) -> &str {
@@ -349,6 +594,7 @@ mod red_hat_boy_states {
         }
     }
 
+    #[derive(Clone, Copy)]
     pub(super) struct Running;
     impl RedHatBoyState<Running> {
         pub(super) fn frame_name(&self) -> &str {
@@ -356,6 +602,7 @@ mod red_hat_boy_states {
         }
     }
 
+    #[derive(Clone, Copy)]
     pub(super) struct Sliding;
     impl RedHatBoyState<Sliding> {
         pub(super) fn frame_name(&self) -> &str {
@@ -363,6 +610,7 @@ mod red_hat_boy_states {
         }
     }
 
+    #[derive(Clone, Copy)]
     pub(super) struct Jumping;
     impl RedHatBoyState<Jumping> {
         pub(super) fn frame_name(&self) -> &str {
@@ -370,6 +618,16 @@ mod red_hat_boy_states {
         }
     }
 
+    // 足場から落ちて空中にいる（Falling と違い、着地すれば走り続けられる）
+    #[derive(Clone, Copy)]
+    pub(super) struct Airborne;
+    impl RedHatBoyState<Airborne> {
+        pub(super) fn frame_name(&self) -> &str {
+            JUMPING_FRAME_NAME
+        }
+    }
+
+    #[derive(Clone, Copy)]
     pub(super) struct Falling;
     impl RedHatBoyState<Falling> {
         pub(super) fn frame_name(&self) -> &str {
@@ -377,6 +635,7 @@ mod red_hat_boy_states {
         }
     }
 
+    #[derive(Clone, Copy)]
     pub(super) struct KnockedOut;
     impl RedHatBoyState<KnockedOut> {
         pub(super) fn frame_name(&self) -> &str {
@@ -388,8 +647,22 @@ mod red_hat_boy_states {
     #[derive(Clone)]
     pub(super) struct RedHatBoyContext {
         pub(super) frame: u8,
-        pub(super) position: Point,
-        pub(super) velocity: Point,
+        pub(super) position: Vector,
+        pub(super) velocity: Vector,
+        pub(super) modifiers: Modifiers,
+        pub(super) physics: PhysicsConfig,
+        // 最後にジャンプキーを押した後に、ジャンプキーを離したかどうか
+        jump_released: bool,
+        // 2 段ジャンプをすでに使ったかどうか
+        double_jumped: bool,
+        // スライディング中にスライディングキーが押され続けているかどうか
+        slide_held: bool,
+        // 直前の update 以降に足場に着地したかどうか
+        grounded: bool,
+        // 足場から走り出た後、まだジャンプできる残りフレーム数
+        coyote_frames: u8,
+        // 空中で押されたジャンプを、着地したときに行う残りフレーム数
+        jump_buffer: u8,
     }
 
     impl RedHatBoyContext {
@@ -402,144 +675,251 @@ mod red_hat_boy_states {
         }
 
         fn update_position(&mut self) {
-            // self.position.x += self.velocity.x;
+            self.position.x += self.velocity.x;
             self.position.y += self.velocity.y;
         }
 
         fn run_right(&mut self) {
-            self.velocity.x = RUNNING_SPEED;
+            self.velocity.x = self.physics.running_speed;
         }
 
         fn run_left(&mut self) {
-            self.velocity.x = -RUNNING_SPEED;
+            self.velocity.x = -self.physics.running_speed;
         }
 
         fn jump(&mut self) {
-            self.velocity.y = JUMP_SPEED;
+            self.velocity.y = self.physics.jump_speed;
+            self.jump_released = false;
+            self.coyote_frames = 0;
+            self.jump_buffer = 0;
         }
 
-        fn land_on(&mut self, y: i16) {
-            self.position.y = y;
+        // ジャンプキーが押され直した場合だけ、着地するまでジャンプを覚えておく
+        // （押し続けている間に着地しても、続けてジャンプはしない）
+        pub(super) fn buffer_jump(&mut self) {
+            if self.jump_released {
+                self.jump_released = false;
+                self.jump_buffer = self.physics.jump_buffer_frames;
+            }
         }
 
-        fn fall(&mut self) {
-            if self.velocity.y < TERMINAL_VELOCITY {
-                self.velocity.y += GRAVITY;
+        // 上昇中にジャンプキーを離すと、上昇の速さを落として低いジャンプにする
+        pub(super) fn release_jump(&mut self) {
+            if !self.jump_released {
+                self.velocity.y = self.velocity.y.max(self.physics.jump_cut_speed);
             }
+            self.jump_released = true;
+        }
+
+        // 2 段ジャンプのパワーアップがあり、一度ジャンプキーを離していれば空中でもう一度ジャンプできる
+        pub(super) fn can_double_jump(&self) -> bool {
+            self.modifiers.is_active(PowerUp::DoubleJump)
+                && self.jump_released
+                && !self.double_jumped
+        }
+
+        // 足場から走り出た直後であれば、空中でもジャンプできる
+        pub(super) fn can_coyote_jump(&self) -> bool {
+            self.coyote_frames > 0
+        }
+
+        // シールドがあれば knock out を防ぐ
+        pub(super) fn can_absorb_hit(&self) -> bool {
+            self.modifiers.can_absorb_hit()
+        }
+
+        fn tick_jump_assist(&mut self) {
+            self.coyote_frames = self.coyote_frames.saturating_sub(1);
+            self.jump_buffer = self.jump_buffer.saturating_sub(1);
+        }
+
+        fn bounce(&mut self) {
+            self.velocity.y = self.physics.bounce_speed;
+        }
+
+        fn bonk(&mut self) {
+            self.velocity.y = self.velocity.y.max(0.);
+        }
+
+        fn tick_modifiers(&mut self) {
+            self.modifiers.tick();
+        }
+
+        pub(super) fn carry(&mut self, dx: i16) {
+            self.position.x += dx as f32;
+        }
+
+        // 足元が y の位置に来るように着地する
+        pub(super) fn land_on(&mut self, y: i16) {
+            self.position.y = (y - self.physics.player_height) as f32;
+            self.velocity.y = 0.;
+            self.grounded = true;
+        }
+
+        fn fall(&mut self) {
+            self.velocity.y =
+                (self.velocity.y + self.physics.gravity).min(self.physics.terminal_velocity);
         }
 
         fn stop(&mut self) {
-            self.velocity.x = 0;
-            self.velocity.y = 0;
+            self.velocity.x = 0.;
+            self.velocity.y = 0.;
         }
     }
 
     // 初期状態の定義
     impl RedHatBoyState<Idle> {
-        pub(super) fn new(position: Point) -> Self {
+        pub(super) fn new(position: Point, physics: PhysicsConfig) -> Self {
             Self {
                 context: RedHatBoyContext {
                     frame: 0,
-                    position,
-                    velocity: Point { x: 0, y: 0 },
+                    position: position.into(),
+                    velocity: Vector { x: 0., y: 0. },
+                    modifiers: Modifiers::default(),
+                    physics,
+                    jump_released: false,
+                    double_jumped: false,
+                    slide_held: false,
+                    // 開始時は FLOOR の地面の上に立っている
+                    grounded: true,
+                    coyote_frames: 0,
+                    jump_buffer: 0,
                 },
                 _state: Idle,
             }
         }
     }
 
-    // 状態遷移を定義
-    impl RedHatBoyState<Idle> {
-        pub(super) fn update(&self) -> RedHatBoyState<Idle> {
+    // どの状態からでも同じように行う状態遷移
+    // （どの状態でこれらを行えるかは遷移表で決める）
+    impl<S> RedHatBoyState<S> {
+        pub(super) fn jump(&self) -> RedHatBoyState<Jumping> {
             let mut context = self.context.clone();
-            context.update_frame(IDLE_FRAME_COUNT);
-            context.update_position();
+            context.reset_frame();
+            context.jump();
+            context.double_jumped = false;
             RedHatBoyState {
                 context,
-                _state: Idle,
+                _state: Jumping,
             }
         }
 
-        pub(super) fn start_run(&self) -> RedHatBoyState<Running> {
+        // 空中から足場に着地して走り出す
+        pub(super) fn land(&self, y: i16) -> RedHatBoyState<Running> {
             let mut context = self.context.clone();
             context.reset_frame();
-            context.run_right();
+            context.land_on(y);
             RedHatBoyState {
                 context,
                 _state: Running,
             }
         }
-    }
 
-    impl RedHatBoyState<Running> {
-        pub(super) fn update(&self) -> RedHatBoyState<Running> {
+        pub(super) fn bounce(&self) -> RedHatBoyState<Jumping> {
             let mut context = self.context.clone();
-            context.update_frame(RUNNING_FRAME_COUNT);
-            context.update_position();
-            if context.position.y >= FLOOR {
-                context.land_on(FLOOR);
-            }
+            context.reset_frame();
+            context.bounce();
             RedHatBoyState {
                 context,
-                _state: Running,
+                _state: Jumping,
             }
         }
 
-        pub(super) fn run_right(&self) -> RedHatBoyState<Running> {
+        pub(super) fn knock_out(&self) -> RedHatBoyState<Falling> {
             let mut context = self.context.clone();
             context.reset_frame();
-            context.run_right();
+            context.stop();
             RedHatBoyState {
                 context,
-                _state: Running,
+                _state: Falling,
             }
         }
+    }
 
-        pub(super) fn run_left(&self) -> RedHatBoyState<Running> {
+    // 状態ごとの状態遷移を定義
+    impl RedHatBoyState<Idle> {
+        pub(super) fn update(&self) -> RedHatBoyState<Idle> {
             let mut context = self.context.clone();
-            context.reset_frame();
-            context.run_left();
+            context.update_frame(context.physics.frame_counts.idle);
+            context.update_position();
+            context.tick_modifiers();
             RedHatBoyState {
                 context,
-                _state: Running,
+                _state: Idle,
             }
         }
 
-        pub(super) fn slide(&self) -> RedHatBoyState<Sliding> {
+        pub(super) fn start_run(&self) -> RedHatBoyState<Running> {
             let mut context = self.context.clone();
             context.reset_frame();
+            context.run_right();
             RedHatBoyState {
                 context,
-                _state: Sliding,
+                _state: Running,
             }
         }
+    }
 
-        pub(super) fn jump(&self) -> RedHatBoyState<Jumping> {
+    pub(super) enum RunningEndState {
+        Running(RedHatBoyState<Running>),
+        Jumping(RedHatBoyState<Jumping>),
+        Airborne(RedHatBoyState<Airborne>),
+    }
+
+    impl RedHatBoyState<Running> {
+        pub(super) fn update(&self) -> RunningEndState {
+            let mut context = self.context.clone();
+            context.update_frame(context.physics.frame_counts.running);
+            context.update_position();
+            context.tick_modifiers();
+            // 足場がなければそのまま落下する（足場との衝突判定で着地させる）
+            context.fall();
+            // 直前のフレームで着地していなければ、足場から走り出て空中にいる
+            if !std::mem::take(&mut context.grounded) {
+                RunningEndState::Airborne(RedHatBoyState::airborne(context))
+            } else if context.jump_buffer > 0 {
+                // 着地する直前に押されていたジャンプを行う
+                RunningEndState::Jumping(
+                    RedHatBoyState {
+                        context,
+                        _state: Running,
+                    }
+                    .jump(),
+                )
+            } else {
+                RunningEndState::Running(RedHatBoyState {
+                    context,
+                    _state: Running,
+                })
+            }
+        }
+
+        pub(super) fn run_right(&self) -> RedHatBoyState<Running> {
             let mut context = self.context.clone();
             context.reset_frame();
-            context.jump();
+            context.run_right();
             RedHatBoyState {
                 context,
-                _state: Jumping,
+                _state: Running,
             }
         }
 
-        pub(super) fn land_on(&self, y: i16) -> RedHatBoyState<Running> {
+        pub(super) fn run_left(&self) -> RedHatBoyState<Running> {
             let mut context = self.context.clone();
-            context.land_on(y - PLAYER_HEIGHT);
+            context.reset_frame();
+            context.run_left();
             RedHatBoyState {
                 context,
                 _state: Running,
             }
         }
 
-        pub(super) fn knock_out(&self) -> RedHatBoyState<Falling> {
+        pub(super) fn slide(&self) -> RedHatBoyState<Sliding> {
             let mut context = self.context.clone();
             context.reset_frame();
-            context.stop();
             RedHatBoyState {
                 context,
-                _state: Falling,
+                _state: Sliding,
             }
         }
     }
@@ -547,17 +927,21 @@ mod red_hat_boy_states {
     pub(super) enum SlidngEndState {
         Sliding(RedHatBoyState<Sliding>),
         Complete(RedHatBoyState<Running>),
+        Airborne(RedHatBoyState<Airborne>),
     }
 
     impl RedHatBoyState<Sliding> {
         pub(super) fn update(&self) -> SlidngEndState {
             let mut context = self.context.clone();
-            context.update_frame(SLIDING_FRAME_COUNT);
+            context.update_frame(context.physics.frame_counts.sliding);
             context.update_position();
-            if context.position.y >= FLOOR {
-                context.land_on(FLOOR);
-            }
-            if context.frame == 0 {
+            context.tick_modifiers();
+            context.fall();
+            // キーが押され続けている間はスライディングを続ける
+            let slide_held = std::mem::take(&mut context.slide_held);
+            if !std::mem::take(&mut context.grounded) {
+                SlidngEndState::Airborne(RedHatBoyState::airborne(context))
+            } else if context.frame == 0 && !slide_held {
                 context.reset_frame();
                 SlidngEndState::Complete(RedHatBoyState {
                     context,
@@ -571,71 +955,74 @@ mod red_hat_boy_states {
             }
         }
 
-        pub(super) fn land_on(&self, y: i16) -> RedHatBoyState<Sliding> {
+        pub(super) fn hold_slide(&self) -> RedHatBoyState<Sliding> {
             let mut context = self.context.clone();
-            context.land_on(y - PLAYER_HEIGHT);
+            context.slide_held = true;
             RedHatBoyState {
                 context,
                 _state: Sliding,
             }
         }
+    }
 
-        pub(super) fn knock_out(&self) -> RedHatBoyState<Falling> {
+    impl RedHatBoyState<Jumping> {
+        pub(super) fn double_jump(&self) -> RedHatBoyState<Jumping> {
             let mut context = self.context.clone();
             context.reset_frame();
-            context.stop();
+            context.jump();
+            context.double_jumped = true;
             RedHatBoyState {
                 context,
-                _state: Falling,
+                _state: Jumping,
             }
         }
-    }
 
-    pub(super) enum JumpEndState {
-        Jumping(RedHatBoyState<Jumping>),
-        Complete(RedHatBoyState<Running>),
-    }
+        // 頭をぶつけたらその場から落ち始める（着地するまではジャンプ中のまま）
+        pub(super) fn bonk(&self) -> RedHatBoyState<Jumping> {
+            let mut context = self.context.clone();
+            context.bonk();
+            RedHatBoyState {
+                context,
+                _state: Jumping,
+            }
+        }
 
-    impl RedHatBoyState<Jumping> {
-        pub(super) fn update(&self) -> JumpEndState {
+        pub(super) fn update(&self) -> RedHatBoyState<Jumping> {
             let mut context = self.context.clone();
-            if context.frame < JUMPING_FRAME_COUNT - 1 {
-                context.update_frame(JUMPING_FRAME_COUNT);
+            if context.frame < context.physics.frame_counts.jumping - 1 {
+                context.update_frame(context.physics.frame_counts.jumping);
             }
             context.update_position();
+            context.tick_modifiers();
+            context.tick_jump_assist();
             context.fall();
-            if context.position.y >= FLOOR {
-                context.land_on(FLOOR);
-                context.reset_frame();
-                JumpEndState::Complete(RedHatBoyState {
-                    context,
-                    _state: Running,
-                })
-            } else {
-                JumpEndState::Jumping(RedHatBoyState {
-                    context,
-                    _state: Jumping,
-                })
+            RedHatBoyState {
+                context,
+                _state: Jumping,
             }
         }
+    }
 
-        pub(super) fn knock_out(&self) -> RedHatBoyState<Falling> {
-            let mut context = self.context.clone();
-            context.reset_frame();
-            context.stop();
+    impl RedHatBoyState<Airborne> {
+        // 足場から落ちた姿勢（ジャンプの最後のフレーム）で空中に移る
+        fn airborne(mut context: RedHatBoyContext) -> Self {
+            context.frame = context.physics.frame_counts.jumping - 1;
+            context.coyote_frames = context.physics.coyote_frames;
             RedHatBoyState {
                 context,
-                _state: Falling,
+                _state: Airborne,
             }
         }
 
-        pub(super) fn land_on(&self, y: i16) -> RedHatBoyState<Running> {
+        pub(super) fn update(&self) -> RedHatBoyState<Airborne> {
             let mut context = self.context.clone();
-            context.reset_frame();
-            context.land_on(y - PLAYER_HEIGHT);
+            context.update_position();
+            context.tick_modifiers();
+            context.tick_jump_assist();
+            context.fall();
             RedHatBoyState {
                 context,
-                _state: Running,
+                _state: Airborne,
             }
         }
     }
@@ -648,14 +1035,11 @@ mod red_hat_boy_states {
     impl RedHatBoyState<Falling> {
         pub(super) fn update(&self) -> FallingEndState {
             let mut context = self.context.clone();
-            context.update_frame(FALLING_FRAME_COUNT);
+            context.update_frame(context.physics.frame_counts.falling);
             context.update_position();
-            if context.position.y < FLOOR {
-                context.fall();
-            } else {
-                context.land_on(FLOOR);
-            }
-            if context.frame == FALLING_FRAME_COUNT - 1 {
+            context.tick_modifiers();
+            context.fall();
+            if context.frame == context.physics.frame_counts.falling - 1 {
                 FallingEndState::Complete(RedHatBoyState {
                     context,
                     _state: KnockedOut,
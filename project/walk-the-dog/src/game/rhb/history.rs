@@ -0,0 +1,217 @@
+// RedHatBoy が受け取ったイベントの履歴
+// 「理由もなく死んだ」といった報告を調べられるように、直近のイベントと状態の変化を記録しておく
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt::Debug,
+    rc::{Rc, Weak},
+};
+
+use serde::{Serialize, Serializer};
+
+use super::red_hat_boy_states::RedHatBoyContext;
+use super::transitions::{EventKind, StateKind};
+use super::Event;
+use crate::engine::renderer::Vector;
+
+// 記録しておくフレーム数（60 fps で 10 秒分。1 フレームに数個のイベントを受け取るので、イベントの数はその数倍になる）
+const CAPACITY_TICKS: u32 = 600;
+
+thread_local! {
+    // ブラウザのコンソールから参照するための、プレイ中の rhb の履歴
+    static CURRENT: RefCell<Weak<RefCell<History>>> = const { RefCell::new(Weak::new()) };
+}
+
+#[derive(Clone, Copy, Serialize)]
+pub(super) struct HistoryEntry {
+    // イベントを受け取ったフレーム（Update を受け取った回数）
+    tick: u32,
+    #[serde(serialize_with = "serialize_debug")]
+    event: Event,
+    from: StateKind,
+    // 遷移後の状態（イベントが無視された場合は None）
+    to: Option<StateKind>,
+    // 遷移後の位置と速度
+    position: Vector,
+    velocity: Vector,
+    // 直前に記録したときから速度が変わったか
+    // （地面に立っている間の Land のように、このフレームの Update の前の速度に戻しただけのものは含めない）
+    velocity_changed: bool,
+}
+
+impl HistoryEntry {
+    // 状態を変えたイベントと、Update 以外で速度を変えたイベントだけをオーバーレイに表示する
+    // 地面を走っている間に毎フレーム受け取る Land や Carry、押し続けているキーの RunRight などは、
+    // 無視されたものも含めて何も変えないので省略する（重力で速度が変わるだけの Update も省略する）
+    #[cfg(feature = "history_debug")]
+    fn is_notable(&self) -> bool {
+        self.to.is_some_and(|to| to != self.from)
+            || (self.velocity_changed && self.event.kind() != EventKind::Update)
+    }
+
+    // 同じイベントで同じ遷移をしたかどうか（オーバーレイでは続けて起きたものを 1 行にまとめる）
+    #[cfg(feature = "history_debug")]
+    fn is_repeat_of(&self, other: &HistoryEntry) -> bool {
+        self.event.kind() == other.event.kind() && self.from == other.from && self.to == other.to
+    }
+}
+
+fn serialize_debug<T: Debug, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{:?}", value))
+}
+
+#[derive(Default)]
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+    tick: u32,
+    // このフレームの Update を受け取る前の速度
+    tick_velocity: Option<Vector>,
+}
+
+impl History {
+    // 新しい履歴を作り、コンソールから参照できるようにする
+    pub(super) fn shared() -> Rc<RefCell<History>> {
+        let history = Rc::new(RefCell::new(History::default()));
+        CURRENT.with(|current| *current.borrow_mut() = Rc::downgrade(&history));
+        history
+    }
+
+    pub(super) fn record(
+        &mut self,
+        event: Event,
+        from: StateKind,
+        to: Option<StateKind>,
+        context: &RedHatBoyContext,
+    ) {
+        let last_velocity = self.entries.back().map(|last| last.velocity);
+        if event.kind() == EventKind::Update {
+            self.tick = self.tick.wrapping_add(1);
+            self.tick_velocity = last_velocity;
+        }
+        let tick = self.tick;
+        while self
+            .entries
+            .front()
+            .is_some_and(|entry| tick.wrapping_sub(entry.tick) >= CAPACITY_TICKS)
+        {
+            self.entries.pop_front();
+        }
+        let velocity = context.velocity;
+        let differs = |other: Option<Vector>| {
+            other.is_none_or(|other| other.x != velocity.x || other.y != velocity.y)
+        };
+        let velocity_changed = differs(last_velocity) && differs(self.tick_velocity);
+        self.entries.push_back(HistoryEntry {
+            tick,
+            event,
+            from,
+            to,
+            position: context.position,
+            velocity: context.velocity,
+            velocity_changed,
+        });
+    }
+
+    // オーバーレイに表示する、状態か速度を変えたイベント
+    #[cfg(feature = "history_debug")]
+    pub(super) fn notable_entries(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter().filter(|entry| entry.is_notable())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.entries).unwrap_or_else(|err| err.to_string())
+    }
+}
+
+// プレイ中の rhb の履歴を JSON にする（rhb がまだ作られていない場合は None）
+pub fn history_json() -> Option<String> {
+    CURRENT.with(|current| {
+        current
+            .borrow()
+            .upgrade()
+            .map(|history| history.borrow().to_json())
+    })
+}
+
+// 履歴をゲーム画面に重ねて表示するデバッグ用のオーバーレイ
+// H キーで表示を切り替える
+#[cfg(feature = "history_debug")]
+pub mod overlay {
+    use super::{History, HistoryEntry};
+    use crate::engine::{
+        key_state::KeyState,
+        renderer::{Point, Renderer},
+    };
+
+    const TOGGLE_KEY: &str = "KeyH";
+    const OVERLAY_POSITION: Point = Point { x: 10, y: 400 };
+    const LINE_HEIGHT: i16 = 24;
+    const LINES: usize = 8;
+
+    #[derive(Default)]
+    pub struct HistoryOverlay {
+        visible: bool,
+        // 直前のフレームでキーが押されていたか（押し続けても 1 回だけ反応させるため）
+        held: bool,
+    }
+
+    impl HistoryOverlay {
+        pub fn update(&mut self, keystate: &KeyState) {
+            let pressed = keystate.is_pressed(TOGGLE_KEY);
+            if pressed && !self.held {
+                self.visible = !self.visible;
+            }
+            self.held = pressed;
+        }
+
+        pub fn draw(&self, renderer: &Renderer, history: &History) {
+            if !self.visible {
+                return;
+            }
+
+            // 続けて起きた同じ遷移は 1 行にまとめ、回数を添える
+            let mut lines: Vec<(&HistoryEntry, usize)> = vec![];
+            history
+                .notable_entries()
+                .for_each(|entry| match lines.last_mut() {
+                    Some((last, count)) if entry.is_repeat_of(last) => {
+                        *last = entry;
+                        *count += 1;
+                    }
+                    _ => lines.push((entry, 1)),
+                });
+
+            lines
+                .iter()
+                .skip(lines.len().saturating_sub(LINES))
+                .enumerate()
+                .for_each(|(line, (entry, count))| {
+                    let to = match entry.to {
+                        Some(to) => format!("{:?}", to),
+                        None => "ignored".to_string(),
+                    };
+                    let repeat = if *count > 1 {
+                        format!(" x{}", count)
+                    } else {
+                        String::new()
+                    };
+                    renderer.draw_text(
+                        &format!(
+                            "{} {:?} {:?}->{} y={:.0} vy={:.1}{}",
+                            entry.tick,
+                            entry.event.kind(),
+                            entry.from,
+                            to,
+                            entry.position.y,
+                            entry.velocity.y,
+                            repeat
+                        ),
+                        &Point {
+                            x: OVERLAY_POSITION.x,
+                            y: OVERLAY_POSITION.y + LINE_HEIGHT * line as i16,
+                        },
+                    );
+                });
+        }
+    }
+}
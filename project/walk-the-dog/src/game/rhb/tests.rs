@@ -0,0 +1,625 @@
+// RedHatBoyStateMachine のテスト
+// スプライトを使わずに状態遷移だけを動かすので、ブラウザがなくても cargo test で実行できる
+use super::transitions::{validate, validate_table, EventKind, StateKind, TRANSITIONS};
+#[cfg(feature = "history_debug")]
+use super::History;
+use super::{Event, RedHatBoyStateMachine, FLOOR, STARTING_POINT};
+use crate::engine::renderer::Point;
+use crate::game::physics::PhysicsConfig;
+use crate::game::power_up::{PowerUp, SLOW_MOTION_SCALE};
+
+// 状態が変わるのを待つフレーム数の上限（テストが終わらなくならないようにする）
+const MAX_FRAMES: usize = 1000;
+
+fn physics() -> PhysicsConfig {
+    PhysicsConfig::default()
+}
+
+// 開始位置（FLOOR）に立ったときの、地面の上端の y 座標
+fn ground() -> i16 {
+    FLOOR + physics().player_height
+}
+
+fn send(machine: &RedHatBoyStateMachine, event: Event) -> RedHatBoyStateMachine {
+    machine
+        .transition(event)
+        .unwrap_or_else(|| panic!("{:?} was ignored while {:?}", event, machine.kind()))
+}
+
+// 1 フレーム進める（ゲームと同じく、update の後に地面との当たり判定で着地させる）
+fn tick(machine: RedHatBoyStateMachine) -> RedHatBoyStateMachine {
+    let machine = machine.transition(Event::Update).unwrap_or(machine);
+    let context = machine.context();
+    if context.position.y >= FLOOR as f32 && context.velocity.y >= 0. {
+        machine.transition(Event::Land(ground())).unwrap_or(machine)
+    } else {
+        machine
+    }
+}
+
+// 状態が変わるまでフレームを進め、変わった後の状態と進めたフレーム数を返す
+fn tick_until_changed(mut machine: RedHatBoyStateMachine) -> (RedHatBoyStateMachine, usize) {
+    let kind = machine.kind();
+    for frames in 1..=MAX_FRAMES {
+        machine = tick(machine);
+        if machine.kind() != kind {
+            return (machine, frames);
+        }
+    }
+    panic!("{:?} did not change within {} frames", kind, MAX_FRAMES);
+}
+
+fn idle() -> RedHatBoyStateMachine {
+    RedHatBoyStateMachine::new(
+        Point {
+            x: STARTING_POINT,
+            y: FLOOR,
+        },
+        physics(),
+    )
+}
+
+fn running() -> RedHatBoyStateMachine {
+    send(&idle(), Event::RunRight)
+}
+
+fn sliding() -> RedHatBoyStateMachine {
+    send(&running(), Event::Slide)
+}
+
+fn jumping() -> RedHatBoyStateMachine {
+    send(&running(), Event::Jump)
+}
+
+// 足場から走り出た直後（まだコヨーテタイムが残っている）
+fn airborne() -> RedHatBoyStateMachine {
+    // 最初の update は、開始時に立っている地面の分だけ着地したままになる
+    let machine = send(&running(), Event::Update);
+    send(&machine, Event::Update)
+}
+
+// 足場から走り出て、コヨーテタイムが過ぎた後
+fn airborne_late() -> RedHatBoyStateMachine {
+    (0..physics().coyote_frames).fold(airborne(), |machine, _| send(&machine, Event::Update))
+}
+
+fn falling() -> RedHatBoyStateMachine {
+    send(&running(), Event::KnockOut)
+}
+
+fn knocked_out() -> RedHatBoyStateMachine {
+    tick_until_changed(falling()).0
+}
+
+fn frame_count(machine: &RedHatBoyStateMachine) -> u8 {
+    let counts = machine.context().physics.frame_counts;
+    match machine.kind() {
+        StateKind::Idle => counts.idle,
+        StateKind::Running => counts.running,
+        StateKind::Sliding => counts.sliding,
+        StateKind::Jumping | StateKind::Airborne => counts.jumping,
+        StateKind::Falling | StateKind::KnockedOut => counts.falling,
+    }
+}
+
+// 各種類のイベントの例
+fn sample_event(kind: EventKind) -> Event {
+    match kind {
+        EventKind::RunRight => Event::RunRight,
+        EventKind::RunLeft => Event::RunLeft,
+        EventKind::Slide => Event::Slide,
+        EventKind::Jump => Event::Jump,
+        EventKind::ReleaseJump => Event::ReleaseJump,
+        EventKind::Update => Event::Update,
+        EventKind::Hit => Event::Hit,
+        EventKind::KnockOut => Event::KnockOut,
+        EventKind::Land => Event::Land(ground()),
+        EventKind::Carry => Event::Carry(3),
+        EventKind::Bounce => Event::Bounce,
+        EventKind::Bonk => Event::Bonk,
+        EventKind::PushOut => Event::PushOut(Point { x: -5, y: 0 }),
+        EventKind::PowerUp => Event::PowerUp(PowerUp::Magnet),
+    }
+}
+
+#[test]
+fn transition_table_is_valid() {
+    assert_eq!(validate(), Vec::<String>::new());
+}
+
+// 無視する行を消すと、その状態がイベントを黙って捨てていることが見つかる
+#[test]
+fn validate_reports_events_a_state_drops_silently() {
+    let context = knocked_out().context().clone();
+    let table: Vec<_> = TRANSITIONS
+        .iter()
+        .filter(|transition| {
+            !(transition.ignores()
+                && transition.matches(StateKind::KnockedOut, EventKind::Update, &context))
+        })
+        .cloned()
+        .collect();
+    assert_eq!(table.len(), TRANSITIONS.len() - 1);
+
+    assert_eq!(
+        validate_table(&table),
+        vec!["KnockedOut does not handle Update".to_string()]
+    );
+}
+
+// すべての状態ですべてのイベントを送り、遷移表どおりに遷移するか、遷移表で無視すると決めたものだけが無視されることと、
+// 遷移表のすべての行が使われることを確かめる
+#[test]
+fn every_transition_follows_the_table() {
+    let shielded = send(&running(), Event::PowerUp(PowerUp::Shield));
+    let double_jump_ready = send(
+        &send(
+            &send(&running(), Event::PowerUp(PowerUp::DoubleJump)),
+            Event::Jump,
+        ),
+        Event::ReleaseJump,
+    );
+    let machines = [
+        idle(),
+        running(),
+        shielded,
+        sliding(),
+        jumping(),
+        double_jump_ready,
+        airborne(),
+        airborne_late(),
+        falling(),
+        knocked_out(),
+    ];
+
+    let mut used = vec![false; TRANSITIONS.len()];
+    for machine in &machines {
+        for kind in EventKind::ALL {
+            let event = sample_event(kind);
+            let row = TRANSITIONS
+                .iter()
+                .position(|transition| transition.matches(machine.kind(), kind, machine.context()));
+            match (row, machine.transition(event)) {
+                (Some(row), Some(next)) => {
+                    assert!(
+                        TRANSITIONS[row].allows(machine.kind(), next.kind()),
+                        "{:?} + {:?} led to {:?}",
+                        machine.kind(),
+                        kind,
+                        next.kind()
+                    );
+                    used[row] = true;
+                }
+                (Some(row), None) if TRANSITIONS[row].ignores() => used[row] = true,
+                (None, None) => panic!(
+                    "{:?} drops {:?} without a row in the transition table",
+                    machine.kind(),
+                    kind
+                ),
+                (row, next) => panic!(
+                    "{:?} + {:?}: table row {:?}, but transition returned {:?}",
+                    machine.kind(),
+                    kind,
+                    row,
+                    next.map(|next| next.kind())
+                ),
+            }
+        }
+    }
+
+    let unused: Vec<usize> = (0..TRANSITIONS.len()).filter(|row| !used[*row]).collect();
+    assert!(unused.is_empty(), "rows {:?} were never used", unused);
+}
+
+#[test]
+fn idle_stays_in_place_until_running() {
+    let mut machine = idle();
+    for _ in 0..100 {
+        machine = tick(machine);
+    }
+    assert_eq!(machine.kind(), StateKind::Idle);
+    assert_eq!(machine.context().position.y, FLOOR as f32);
+
+    let machine = send(&machine, Event::RunRight);
+    assert_eq!(machine.kind(), StateKind::Running);
+    assert_eq!(machine.context().velocity.x, physics().running_speed);
+    assert_eq!(machine.context().frame, 0);
+}
+
+#[test]
+fn idle_ignores_actions() {
+    let machine = idle();
+    for event in [Event::Jump, Event::Slide, Event::Hit, Event::KnockOut] {
+        assert!(machine.transition(event).is_none(), "{:?}", event);
+    }
+}
+
+#[test]
+fn running_changes_direction() {
+    let machine = send(&running(), Event::RunLeft);
+    assert_eq!(machine.kind(), StateKind::Running);
+    assert_eq!(machine.context().velocity.x, -physics().running_speed);
+
+    let machine = send(&machine, Event::RunRight);
+    assert_eq!(machine.context().velocity.x, physics().running_speed);
+}
+
+#[test]
+fn running_stays_on_the_ground() {
+    let mut machine = running();
+    for _ in 0..100 {
+        machine = tick(machine);
+        assert_eq!(machine.kind(), StateKind::Running);
+        assert_eq!(machine.context().position.y, FLOOR as f32);
+    }
+}
+
+#[test]
+fn sliding_completes_after_one_animation() {
+    let (machine, frames) = tick_until_changed(sliding());
+    assert_eq!(machine.kind(), StateKind::Running);
+    assert_eq!(frames, physics().frame_counts.sliding as usize);
+    assert_eq!(machine.context().frame, 0);
+}
+
+#[test]
+fn sliding_continues_while_held() {
+    let mut machine = sliding();
+    for _ in 0..physics().frame_counts.sliding as usize * 3 {
+        machine = tick(send(&machine, Event::Slide));
+        assert_eq!(machine.kind(), StateKind::Sliding);
+    }
+
+    let (machine, _) = tick_until_changed(machine);
+    assert_eq!(machine.kind(), StateKind::Running);
+}
+
+#[test]
+fn jump_arc_returns_to_the_floor() {
+    let mut machine = jumping();
+    assert_eq!(machine.context().velocity.y, physics().jump_speed);
+
+    let mut highest = FLOOR as f32;
+    let mut frames = 0;
+    while machine.kind() == StateKind::Jumping {
+        machine = tick(machine);
+        highest = highest.min(machine.context().position.y);
+        frames += 1;
+        assert!(frames < MAX_FRAMES, "never landed");
+    }
+
+    // 初速 v、重力 g で投げ上げたときの高さ v^2 / 2g 程度まで上がる
+    let speed = physics().jump_speed;
+    let expected = speed * speed / (2. * physics().gravity);
+    let height = FLOOR as f32 - highest;
+    assert!(
+        (height - expected).abs() < -speed,
+        "height {} (expected about {})",
+        height,
+        expected
+    );
+    assert_eq!(machine.kind(), StateKind::Running);
+    assert_eq!(machine.context().position.y, FLOOR as f32);
+    assert_eq!(machine.context().velocity.y, 0.);
+}
+
+#[test]
+fn releasing_jump_early_makes_a_lower_jump() {
+    let peak = |release_after: Option<usize>| {
+        let mut machine = jumping();
+        let mut highest = FLOOR as f32;
+        for frame in 0..MAX_FRAMES {
+            if release_after.is_some_and(|release_after| frame >= release_after) {
+                machine = send(&machine, Event::ReleaseJump);
+            }
+            machine = tick(machine);
+            highest = highest.min(machine.context().position.y);
+            if machine.kind() != StateKind::Jumping {
+                break;
+            }
+        }
+        FLOOR as f32 - highest
+    };
+
+    let full = peak(None);
+    let short = peak(Some(2));
+    assert!(short < full / 2., "short {} vs full {}", short, full);
+}
+
+#[test]
+fn release_jump_cuts_upward_speed() {
+    let machine = send(&jumping(), Event::ReleaseJump);
+    assert_eq!(machine.context().velocity.y, physics().jump_cut_speed);
+
+    // 落下中に離しても速さは変わらない
+    let mut machine = jumping();
+    while machine.context().velocity.y <= 0. {
+        machine = tick(machine);
+    }
+    let velocity = machine.context().velocity.y;
+    let machine = send(&machine, Event::ReleaseJump);
+    assert_eq!(machine.context().velocity.y, velocity);
+}
+
+#[test]
+fn double_jump_needs_power_up_and_release() {
+    let machine = send(&running(), Event::PowerUp(PowerUp::DoubleJump));
+    let machine = tick(send(&machine, Event::Jump));
+
+    // ジャンプキーを押し続けていても 2 段ジャンプにはならない
+    let held = send(&machine, Event::Jump);
+    assert_eq!(held.kind(), StateKind::Jumping);
+    assert!(held.context().velocity.y > physics().jump_speed);
+
+    let released = send(&machine, Event::ReleaseJump);
+    let jumped = send(&released, Event::Jump);
+    assert_eq!(jumped.context().velocity.y, physics().jump_speed);
+    assert_eq!(jumped.context().frame, 0);
+
+    // 2 段ジャンプは 1 回だけ
+    let again = send(&send(&jumped, Event::ReleaseJump), Event::Jump);
+    assert!(!again.context().can_double_jump());
+    assert!(again.context().velocity.y > physics().jump_speed);
+}
+
+#[test]
+fn jump_pressed_before_landing_is_buffered() {
+    let mut machine = send(&jumping(), Event::ReleaseJump);
+    // 地面に着く 2 フレームほど前まで進める
+    while machine.context().velocity.y <= 0.
+        || machine.context().position.y < FLOOR as f32 - 2. * machine.context().velocity.y
+    {
+        machine = tick(machine);
+    }
+    machine = send(&machine, Event::Jump);
+    assert_eq!(machine.kind(), StateKind::Jumping);
+
+    let (landed, _) = tick_until_changed(machine);
+    assert_eq!(landed.kind(), StateKind::Running);
+    let jumped = tick(landed);
+    assert_eq!(jumped.kind(), StateKind::Jumping);
+    assert_eq!(jumped.context().velocity.y, physics().jump_speed);
+}
+
+#[test]
+fn running_off_a_ledge_becomes_airborne() {
+    let machine = airborne();
+    assert_eq!(machine.kind(), StateKind::Airborne);
+    assert!(machine.context().can_coyote_jump());
+    assert_eq!(machine.context().frame, physics().frame_counts.jumping - 1);
+
+    let landed = send(&machine, Event::Land(ground()));
+    assert_eq!(landed.kind(), StateKind::Running);
+    assert_eq!(landed.context().position.y, FLOOR as f32);
+}
+
+#[test]
+fn coyote_jump_only_right_after_leaving_the_ledge() {
+    let jumped = send(&airborne(), Event::Jump);
+    assert_eq!(jumped.kind(), StateKind::Jumping);
+    assert_eq!(jumped.context().velocity.y, physics().jump_speed);
+
+    let late = airborne_late();
+    assert!(!late.context().can_coyote_jump());
+    assert_eq!(send(&late, Event::Jump).kind(), StateKind::Airborne);
+}
+
+#[test]
+fn sliding_off_a_ledge_becomes_airborne() {
+    let machine = send(&sliding(), Event::Update);
+    let machine = send(&machine, Event::Update);
+    assert_eq!(machine.kind(), StateKind::Airborne);
+}
+
+#[test]
+fn hits_knock_out_unless_shielded() {
+    for machine in [running(), sliding(), jumping(), airborne()] {
+        let kind = machine.kind();
+        assert_eq!(
+            send(&machine, Event::Hit).kind(),
+            StateKind::Falling,
+            "{:?}",
+            kind
+        );
+    }
+
+    let shielded = send(&running(), Event::PowerUp(PowerUp::Shield));
+    let machine = send(&shielded, Event::Hit);
+    assert_eq!(machine.kind(), StateKind::Running);
+    assert!(!machine.context().modifiers.is_active(PowerUp::Shield));
+
+    // 防いだ直後の無敵時間が終わると、再びダメージを受ける
+    let mut machine = machine;
+    let mut frames = 0;
+    while machine.context().can_absorb_hit() {
+        assert_eq!(send(&machine, Event::Hit).kind(), StateKind::Running);
+        machine = tick(machine);
+        frames += 1;
+        assert!(frames < MAX_FRAMES, "invulnerable forever");
+    }
+    assert_eq!(send(&machine, Event::Hit).kind(), StateKind::Falling);
+}
+
+#[test]
+fn knock_out_ignores_shield() {
+    let shielded = send(&running(), Event::PowerUp(PowerUp::Shield));
+    assert_eq!(send(&shielded, Event::KnockOut).kind(), StateKind::Falling);
+}
+
+#[test]
+fn falling_ends_knocked_out_on_the_floor() {
+    let machine = falling();
+    assert_eq!(machine.context().velocity.x, 0.);
+
+    let (machine, frames) = tick_until_changed(machine);
+    assert_eq!(machine.kind(), StateKind::KnockedOut);
+    assert_eq!(frames, physics().frame_counts.falling as usize - 1);
+    assert!(machine.context().position.y <= FLOOR as f32);
+
+    // KnockedOut になった後は何も受け付けない（状態を変えないイベントを除く）
+    for kind in EventKind::ALL {
+        let event = sample_event(kind);
+        let any_state = matches!(
+            kind,
+            EventKind::PowerUp | EventKind::PushOut | EventKind::ReleaseJump
+        );
+        assert_eq!(machine.transition(event).is_some(), any_state, "{:?}", kind);
+    }
+}
+
+#[test]
+fn bounce_jumps_from_any_moving_state() {
+    for machine in [running(), sliding(), jumping(), airborne()] {
+        let kind = machine.kind();
+        let bounced = send(&machine, Event::Bounce);
+        assert_eq!(bounced.kind(), StateKind::Jumping, "{:?}", kind);
+        assert_eq!(bounced.context().velocity.y, physics().bounce_speed);
+    }
+}
+
+#[test]
+fn bonk_stops_rising() {
+    let machine = send(&jumping(), Event::Bonk);
+    assert_eq!(machine.kind(), StateKind::Jumping);
+    assert_eq!(machine.context().velocity.y, 0.);
+
+    // 落下中は速さを変えない
+    let mut machine = jumping();
+    while machine.context().velocity.y <= 0. {
+        machine = tick(machine);
+    }
+    let velocity = machine.context().velocity.y;
+    assert_eq!(send(&machine, Event::Bonk).context().velocity.y, velocity);
+}
+
+#[test]
+fn carry_and_push_out_move_without_changing_state() {
+    for machine in [running(), sliding()] {
+        let x = machine.context().position.x;
+        let carried = send(&machine, Event::Carry(3));
+        assert_eq!(carried.kind(), machine.kind());
+        assert_eq!(carried.context().position.x, x + 3.);
+    }
+
+    // 押し戻された位置から、そのまま走り続ける
+    let machine = send(&running(), Event::PushOut(Point { x: -5, y: 0 }));
+    assert_eq!(machine.context().position.x, (STARTING_POINT - 5) as f32);
+    let machine = (0..5).fold(machine, |machine, _| tick(machine));
+    assert_eq!(
+        machine.context().position.x,
+        (STARTING_POINT - 5) as f32 + physics().running_speed * 5.
+    );
+}
+
+#[test]
+fn power_ups_expire_in_real_time() {
+    let machine = send(&running(), Event::PowerUp(PowerUp::Magnet));
+    assert!(machine.context().modifiers.is_active(PowerUp::Magnet));
+    let remaining = machine.context().modifiers.remaining(PowerUp::Magnet);
+
+    // 残り時間はゲームの update ではなく実時間で減る
+    let machine = tick(machine);
+    assert_eq!(
+        machine.context().modifiers.remaining(PowerUp::Magnet),
+        remaining
+    );
+
+    let mut modifiers = machine.context().modifiers.clone();
+    (0..remaining - 1).for_each(|_| modifiers.elapse(1.));
+    assert!(modifiers.is_active(PowerUp::Magnet));
+    modifiers.elapse(1.);
+    assert!(!modifiers.is_active(PowerUp::Magnet));
+
+    // スローモーション中は update 1 回で 1 / SLOW_MOTION_SCALE フレーム進むので、実時間では同じ長さで切れる
+    let mut modifiers = machine.context().modifiers.clone();
+    let updates = (remaining as f32 * SLOW_MOTION_SCALE) as u16;
+    (0..updates).for_each(|_| modifiers.elapse(1. / SLOW_MOTION_SCALE));
+    assert!(!modifiers.is_active(PowerUp::Magnet));
+}
+
+// いろいろな入力を続けても、地面より下に沈まず、アニメーションのフレームが範囲内に収まる
+#[test]
+fn physics_invariants_hold_for_a_long_run() {
+    let mut machine = running();
+    for frame in 0..MAX_FRAMES * 3 {
+        // 一定の周期でジャンプ・スライディング・方向転換を混ぜる
+        let events: &[Event] = match frame % 97 {
+            0..=4 => &[Event::Jump],
+            20..=24 => &[Event::Jump, Event::ReleaseJump],
+            40..=60 => &[Event::Slide],
+            70 => &[Event::Bounce],
+            80 => &[Event::RunLeft],
+            85 => &[Event::RunRight],
+            _ => &[Event::ReleaseJump],
+        };
+        for event in events {
+            machine = machine.transition(*event).unwrap_or(machine);
+        }
+        machine = tick(machine);
+
+        assert!(
+            machine.context().position.y <= FLOOR as f32,
+            "below the floor at frame {} while {:?}",
+            frame,
+            machine.kind()
+        );
+        assert!(
+            machine.context().frame < frame_count(&machine),
+            "frame {} out of range while {:?}",
+            machine.context().frame,
+            machine.kind()
+        );
+        assert!(machine.context().velocity.y <= physics().terminal_velocity);
+    }
+    assert_ne!(machine.kind(), StateKind::Falling);
+}
+
+// RedHatBoy と同じく、イベントを送って履歴に記録する
+#[cfg(feature = "history_debug")]
+fn record(
+    history: &mut History,
+    machine: RedHatBoyStateMachine,
+    event: Event,
+) -> RedHatBoyStateMachine {
+    let from = machine.kind();
+    let next = machine.transition(event);
+    let to = next.as_ref().map(|next| next.kind());
+    let machine = next.unwrap_or(machine);
+    history.record(event, from, to, machine.context());
+    machine
+}
+
+// 地面を走り続けている間に毎フレーム受け取るイベントで、オーバーレイが埋まらない
+#[cfg(feature = "history_debug")]
+#[test]
+fn running_on_the_ground_is_not_notable() {
+    let mut history = History::default();
+    let mut machine = record(&mut history, idle(), Event::RunRight);
+    let notable = history.notable_entries().count();
+
+    for _ in 0..100 {
+        for event in [
+            Event::Update,
+            Event::Land(ground()),
+            Event::Carry(2),
+            Event::RunRight,
+            Event::ReleaseJump,
+        ] {
+            machine = record(&mut history, machine, event);
+        }
+    }
+    assert_eq!(history.notable_entries().count(), notable);
+
+    // ジャンプすると状態が変わり、表示される
+    let mut machine = record(&mut history, machine, Event::Jump);
+    assert_eq!(history.notable_entries().count(), notable + 1);
+
+    // 上昇中に重力で速度が変わるだけのフレームと、押し続けている間は無視されるキーは表示しない
+    for _ in 0..5 {
+        for event in [Event::Update, Event::RunRight] {
+            machine = record(&mut history, machine, event);
+        }
+    }
+    assert_eq!(machine.kind(), StateKind::Jumping);
+    assert_eq!(history.notable_entries().count(), notable + 1);
+}
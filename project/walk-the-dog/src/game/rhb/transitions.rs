@@ -0,0 +1,473 @@
+// RedHatBoy の状態遷移表
+// 状態遷移はすべてこの表に行として書き、表から検証や状態遷移図の出力を行う
+use serde::Serialize;
+
+use super::red_hat_boy_states::*;
+use super::{Event, RedHatBoyStateMachine};
+
+// 状態の種類
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize)]
+pub(super) enum StateKind {
+    Idle,
+    Running,
+    Sliding,
+    Jumping,
+    Airborne,
+    Falling,
+    KnockedOut,
+}
+
+impl StateKind {
+    pub(super) const ALL: [StateKind; 7] = [
+        StateKind::Idle,
+        StateKind::Running,
+        StateKind::Sliding,
+        StateKind::Jumping,
+        StateKind::Airborne,
+        StateKind::Falling,
+        StateKind::KnockedOut,
+    ];
+}
+
+// イベントの種類（イベントが持つ値を除いたもの）
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(super) enum EventKind {
+    RunRight,
+    RunLeft,
+    Slide,
+    Jump,
+    ReleaseJump,
+    Update,
+    Hit,
+    KnockOut,
+    Land,
+    Carry,
+    Bounce,
+    Bonk,
+    PushOut,
+    PowerUp,
+}
+
+impl EventKind {
+    pub(super) const ALL: [EventKind; 14] = [
+        EventKind::RunRight,
+        EventKind::RunLeft,
+        EventKind::Slide,
+        EventKind::Jump,
+        EventKind::ReleaseJump,
+        EventKind::Update,
+        EventKind::Hit,
+        EventKind::KnockOut,
+        EventKind::Land,
+        EventKind::Carry,
+        EventKind::Bounce,
+        EventKind::Bonk,
+        EventKind::PushOut,
+        EventKind::PowerUp,
+    ];
+}
+
+impl Event {
+    pub(super) fn kind(&self) -> EventKind {
+        match self {
+            Event::RunRight => EventKind::RunRight,
+            Event::RunLeft => EventKind::RunLeft,
+            Event::Slide => EventKind::Slide,
+            Event::Jump => EventKind::Jump,
+            Event::ReleaseJump => EventKind::ReleaseJump,
+            Event::Update => EventKind::Update,
+            Event::Hit => EventKind::Hit,
+            Event::KnockOut => EventKind::KnockOut,
+            Event::Land(_) => EventKind::Land,
+            Event::Carry(_) => EventKind::Carry,
+            Event::Bounce => EventKind::Bounce,
+            Event::Bonk => EventKind::Bonk,
+            Event::PushOut(_) => EventKind::PushOut,
+            Event::PowerUp(_) => EventKind::PowerUp,
+        }
+    }
+}
+
+// 遷移を行うための条件
+#[derive(Clone)]
+pub(super) struct Guard {
+    name: &'static str,
+    check: fn(&RedHatBoyContext) -> bool,
+}
+
+// 遷移表の 1 行
+// from の状態（None の場合はすべての状態）で event を受け取り、guard を満たしていれば action で次の状態を作る
+#[derive(Clone)]
+pub(super) struct Transition {
+    from: Option<StateKind>,
+    event: EventKind,
+    guard: Option<Guard>,
+    // action で遷移しうる状態（空の場合は状態を変えない）
+    to: &'static [StateKind],
+    action: Action,
+}
+
+#[derive(Clone)]
+pub(super) enum Action {
+    Apply(fn(&RedHatBoyStateMachine, Event) -> RedHatBoyStateMachine),
+    // その状態では受け取っても何もしないと決めているイベント（ログにも出さない）
+    Ignore,
+}
+
+impl Transition {
+    pub(super) fn matches(
+        &self,
+        state: StateKind,
+        event: EventKind,
+        context: &RedHatBoyContext,
+    ) -> bool {
+        self.from.is_none_or(|from| from == state)
+            && self.event == event
+            && self
+                .guard
+                .as_ref()
+                .is_none_or(|guard| (guard.check)(context))
+    }
+
+    // 次の状態を作る（無視する行の場合は None）
+    pub(super) fn apply(
+        &self,
+        machine: &RedHatBoyStateMachine,
+        event: Event,
+    ) -> Option<RedHatBoyStateMachine> {
+        match self.action {
+            Action::Apply(action) => Some(action(machine, event)),
+            Action::Ignore => None,
+        }
+    }
+
+    pub(super) fn ignores(&self) -> bool {
+        matches!(self.action, Action::Ignore)
+    }
+
+    // from から to への遷移が、この行に書かれたものかどうか
+    pub(super) fn allows(&self, from: StateKind, to: StateKind) -> bool {
+        if self.to.is_empty() {
+            from == to
+        } else {
+            self.to.contains(&to)
+        }
+    }
+
+    fn label(&self) -> String {
+        match &self.guard {
+            Some(guard) => format!("{:?} [{}]", self.event, guard.name),
+            None => format!("{:?}", self.event),
+        }
+    }
+}
+
+// from の状態で event を受け取ったときの行を作る
+// |state, pattern| の state には from の状態が、pattern には event を当てはめたものが入る
+// `=> ignore` と書いた行は、その状態ではそのイベントを無視する
+macro_rules! on {
+    ($from:ident + $event:ident => ignore) => {
+        Transition {
+            from: Some(StateKind::$from),
+            event: EventKind::$event,
+            guard: None,
+            to: &[],
+            action: Action::Ignore,
+        }
+    };
+    (@guard) => {
+        None
+    };
+    (@guard $guard:ident) => {
+        Some(Guard {
+            name: stringify!($guard),
+            check: RedHatBoyContext::$guard,
+        })
+    };
+    ($from:ident + $event:ident $(if $guard:ident)? => [$($to:ident),+],
+        |$state:ident, $pattern:pat_param| $action:expr) => {
+        Transition {
+            from: Some(StateKind::$from),
+            event: EventKind::$event,
+            guard: on!(@guard $($guard)?),
+            to: &[$(StateKind::$to),+],
+            action: Action::Apply(|machine, event| match (machine, event) {
+                (RedHatBoyStateMachine::$from($state), $pattern) => $action.into(),
+                _ => unreachable!(),
+            }),
+        }
+    };
+}
+
+// 上の行ほど優先される
+pub(super) const TRANSITIONS: &[Transition] = &[
+    // どの状態でも状態を変えずに context だけを変えるもの
+    Transition {
+        from: None,
+        event: EventKind::PowerUp,
+        guard: None,
+        to: &[],
+        action: Action::Apply(|machine, event| match event {
+            Event::PowerUp(power_up) => {
+                machine.map_context(|context| context.modifiers.grant(power_up))
+            }
+            _ => unreachable!(),
+        }),
+    },
+    Transition {
+        from: None,
+        event: EventKind::PushOut,
+        guard: None,
+        to: &[],
+        action: Action::Apply(|machine, event| match event {
+            Event::PushOut(offset) => {
+                machine.map_context(|context| context.position.move_by(offset))
+            }
+            _ => unreachable!(),
+        }),
+    },
+    Transition {
+        from: None,
+        event: EventKind::Hit,
+        guard: Some(Guard {
+            name: "can_absorb_hit",
+            check: RedHatBoyContext::can_absorb_hit,
+        }),
+        to: &[],
+        action: Action::Apply(|machine, _| {
+            machine.map_context(|context| context.modifiers.absorb_hit())
+        }),
+    },
+    Transition {
+        from: None,
+        event: EventKind::ReleaseJump,
+        guard: None,
+        to: &[],
+        action: Action::Apply(|machine, _| machine.map_context(|context| context.release_jump())),
+    },
+    // Idle
+    on!(Idle + RunRight => [Running], |state, _| state.start_run()),
+    on!(Idle + Update => [Idle], |state, _| state.update()),
+    // 走り出すまでは地面に立っているだけで、障害物も届かない
+    on!(Idle + RunLeft => ignore),
+    on!(Idle + Slide => ignore),
+    on!(Idle + Jump => ignore),
+    on!(Idle + Hit => ignore),
+    on!(Idle + KnockOut => ignore),
+    on!(Idle + Land => ignore),
+    on!(Idle + Carry => ignore),
+    on!(Idle + Bounce => ignore),
+    on!(Idle + Bonk => ignore),
+    // Running
+    on!(Running + RunLeft => [Running], |state, _| state.run_left()),
+    on!(Running + RunRight => [Running], |state, _| state.run_right()),
+    on!(Running + Slide => [Sliding], |state, _| state.slide()),
+    on!(Running + Jump => [Jumping], |state, _| state.jump()),
+    on!(Running + Hit => [Falling], |state, _| state.knock_out()),
+    on!(Running + KnockOut => [Falling], |state, _| state.knock_out()),
+    on!(Running + Land => [Running], |state, Event::Land(y)| state
+        .map_context(|context| context.land_on(y))),
+    // 動く踏み台に運ばれる
+    on!(Running + Carry => [Running], |state, Event::Carry(dx)| state
+        .map_context(|context| context.carry(dx))),
+    on!(Running + Bounce => [Jumping], |state, _| state.bounce()),
+    on!(Running + Update => [Running, Jumping, Airborne], |state, _| state.update()),
+    // 上昇していないので、頭をぶつけても何も変わらない
+    on!(Running + Bonk => ignore),
+    // Sliding
+    on!(Sliding + Slide => [Sliding], |state, _| state.hold_slide()),
+    on!(Sliding + Hit => [Falling], |state, _| state.knock_out()),
+    on!(Sliding + KnockOut => [Falling], |state, _| state.knock_out()),
+    on!(Sliding + Land => [Sliding], |state, Event::Land(y)| state
+        .map_context(|context| context.land_on(y))),
+    on!(Sliding + Carry => [Sliding], |state, Event::Carry(dx)| state
+        .map_context(|context| context.carry(dx))),
+    on!(Sliding + Bounce => [Jumping], |state, _| state.bounce()),
+    on!(Sliding + Update => [Sliding, Running, Airborne], |state, _| state.update()),
+    // スライディングが終わるまでは向きを変えたりジャンプしたりできない
+    on!(Sliding + RunRight => ignore),
+    on!(Sliding + RunLeft => ignore),
+    on!(Sliding + Jump => ignore),
+    on!(Sliding + Bonk => ignore),
+    // Jumping
+    on!(Jumping + Jump if can_double_jump => [Jumping], |state, _| state.double_jump()),
+    // 空中で押されたジャンプは、着地したときに行う
+    on!(Jumping + Jump => [Jumping], |state, _| state
+        .map_context(|context| context.buffer_jump())),
+    on!(Jumping + Hit => [Falling], |state, _| state.knock_out()),
+    on!(Jumping + KnockOut => [Falling], |state, _| state.knock_out()),
+    on!(Jumping + Land => [Running], |state, Event::Land(y)| state.land(y)),
+    on!(Jumping + Bonk => [Jumping], |state, _| state.bonk()),
+    on!(Jumping + Bounce => [Jumping], |state, _| state.bounce()),
+    on!(Jumping + Update => [Jumping], |state, _| state.update()),
+    // 空中では向きを変えられず、踏み台にも運ばれない
+    on!(Jumping + RunRight => ignore),
+    on!(Jumping + RunLeft => ignore),
+    on!(Jumping + Slide => ignore),
+    on!(Jumping + Carry => ignore),
+    // Airborne
+    on!(Airborne + Jump if can_coyote_jump => [Jumping], |state, _| state.jump()),
+    on!(Airborne + Jump => [Airborne], |state, _| state
+        .map_context(|context| context.buffer_jump())),
+    on!(Airborne + Hit => [Falling], |state, _| state.knock_out()),
+    on!(Airborne + KnockOut => [Falling], |state, _| state.knock_out()),
+    on!(Airborne + Land => [Running], |state, Event::Land(y)| state.land(y)),
+    on!(Airborne + Bounce => [Jumping], |state, _| state.bounce()),
+    on!(Airborne + Update => [Airborne], |state, _| state.update()),
+    on!(Airborne + RunRight => ignore),
+    on!(Airborne + RunLeft => ignore),
+    on!(Airborne + Slide => ignore),
+    on!(Airborne + Carry => ignore),
+    // 落ちているだけなので、頭をぶつけても速さは変わらない
+    on!(Airborne + Bonk => ignore),
+    // Falling
+    on!(Falling + Land => [Falling], |state, Event::Land(y)| state
+        .map_context(|context| context.land_on(y))),
+    on!(Falling + Update => [Falling, KnockedOut], |state, _| state.update()),
+    // 倒れている途中は入力を受け付けず、すでに倒されているので障害物や穴も関係ない
+    // （穴に落ちて画面の下に出ている間は、毎フレーム KnockOut を受け取る）
+    on!(Falling + RunRight => ignore),
+    on!(Falling + RunLeft => ignore),
+    on!(Falling + Slide => ignore),
+    on!(Falling + Jump => ignore),
+    on!(Falling + Hit => ignore),
+    on!(Falling + KnockOut => ignore),
+    on!(Falling + Carry => ignore),
+    on!(Falling + Bounce => ignore),
+    on!(Falling + Bonk => ignore),
+    // KnockedOut
+    // 倒れた後は何も受け付けない（重なったままの石からは毎フレーム Hit を受け取る）
+    on!(KnockedOut + RunRight => ignore),
+    on!(KnockedOut + RunLeft => ignore),
+    on!(KnockedOut + Slide => ignore),
+    on!(KnockedOut + Jump => ignore),
+    on!(KnockedOut + Update => ignore),
+    on!(KnockedOut + Hit => ignore),
+    on!(KnockedOut + KnockOut => ignore),
+    on!(KnockedOut + Land => ignore),
+    on!(KnockedOut + Carry => ignore),
+    on!(KnockedOut + Bounce => ignore),
+    on!(KnockedOut + Bonk => ignore),
+];
+
+// 遷移表の誤りを探して、見つかった問題を返す
+pub(super) fn validate() -> Vec<String> {
+    validate_table(TRANSITIONS)
+}
+
+pub(super) fn validate_table(table: &[Transition]) -> Vec<String> {
+    let mut problems = vec![];
+
+    // Idle から辿り着けない状態
+    let mut reachable = vec![StateKind::Idle];
+    let mut index = 0;
+    while let Some(&state) = reachable.get(index) {
+        table
+            .iter()
+            .filter(|transition| transition.from == Some(state))
+            .flat_map(|transition| transition.to.iter())
+            .for_each(|to| {
+                if !reachable.contains(to) {
+                    reachable.push(*to);
+                }
+            });
+        index += 1;
+    }
+    StateKind::ALL
+        .iter()
+        .filter(|state| !reachable.contains(state))
+        .for_each(|state| problems.push(format!("{:?} is unreachable", state)));
+
+    // 状態ごとに、遷移も無視もしないイベント（条件付きの行だけでは、条件を満たさないときに扱われない）
+    StateKind::ALL.iter().for_each(|state| {
+        EventKind::ALL
+            .iter()
+            .filter(|event| {
+                !table.iter().any(|transition| {
+                    transition.guard.is_none()
+                        && transition.event == **event
+                        && transition.from.is_none_or(|from| from == *state)
+                })
+            })
+            .for_each(|event| problems.push(format!("{:?} does not handle {:?}", state, event)));
+    });
+
+    // 前の行に必ず先に当てはまるので、使われることのない行
+    table.iter().enumerate().for_each(|(index, transition)| {
+        let shadowed = table[..index].iter().any(|earlier| {
+            earlier.guard.is_none()
+                && earlier.event == transition.event
+                && (earlier.from.is_none() || earlier.from == transition.from)
+        });
+        if shadowed {
+            problems.push(format!(
+                "{:?} + {} is shadowed by an earlier row",
+                transition.from,
+                transition.label()
+            ));
+        }
+    });
+
+    problems
+}
+
+// 状態遷移図の形式
+#[derive(Clone, Copy)]
+pub enum DiagramFormat {
+    Mermaid,
+    Graphviz,
+}
+
+// 遷移表から状態遷移図を作る
+// すべての状態で状態を変えずに扱うイベントは、矢印ではなく図のタイトルに並べる
+pub fn state_diagram(format: DiagramFormat) -> String {
+    let any_state = TRANSITIONS
+        .iter()
+        .filter(|transition| transition.from.is_none())
+        .map(|transition| transition.label())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let title = format!("RedHatBoy (any state: {})", any_state);
+    let edges = TRANSITIONS.iter().flat_map(|transition| {
+        transition
+            .from
+            .into_iter()
+            .flat_map(move |from| transition.to.iter().map(move |to| (from, *to, transition)))
+    });
+
+    let mut lines = vec![];
+    match format {
+        DiagramFormat::Mermaid => {
+            lines.push("---".to_string());
+            lines.push(format!("title: {}", title));
+            lines.push("---".to_string());
+            lines.push("stateDiagram-v2".to_string());
+            lines.push(format!("    [*] --> {:?}", StateKind::Idle));
+            edges.for_each(|(from, to, transition)| {
+                lines.push(format!(
+                    "    {:?} --> {:?}: {}",
+                    from,
+                    to,
+                    transition.label()
+                ))
+            });
+        }
+        DiagramFormat::Graphviz => {
+            lines.push("digraph RedHatBoy {".to_string());
+            lines.push(format!("    label=\"{}\";", title));
+            lines.push("    labelloc=t;".to_string());
+            lines.push("    start [shape=point];".to_string());
+            lines.push(format!("    start -> {:?};", StateKind::Idle));
+            edges.for_each(|(from, to, transition)| {
+                lines.push(format!(
+                    "    {:?} -> {:?} [label=\"{}\"];",
+                    from,
+                    to,
+                    transition.label()
+                ))
+            });
+            lines.push("}".to_string());
+        }
+    }
+    lines.join("\n")
+}
@@ -1,4 +1,5 @@
 use engine::GameLoop;
+use game::DiagramFormat;
 use wasm_bindgen::prelude::*;
 
 mod sierpinski;
@@ -6,11 +7,13 @@ mod sierpinski;
 #[macro_use]
 mod browser;
 
-mod engine;
+// ゲームのロジックはブラウザに依存しない部分をネイティブでもビルドし、tests/ からテストできるように公開する
+// （ブラウザに依存する部分は browser と、engine 内の target_arch = "wasm32" の部分に閉じ込める）
+pub mod engine;
 
-mod game;
+pub mod game;
 
-mod segments;
+pub mod segments;
 
 // This is like the `main` function, except for JavaScript.
 #[wasm_bindgen(start)]
@@ -25,8 +28,27 @@ pub fn main_js() -> Result<(), JsValue> {
 
         GameLoop::start(game)
             .await
-            .expect("Error starting game loop")
+            .expect("Error starting game loop");
     });
 
     Ok(())
 }
+
+// RedHatBoy の状態遷移図を返す（format は "mermaid" か "graphviz"）
+// ブラウザのコンソールから呼び出して、ドキュメントやレビューに使う
+#[wasm_bindgen]
+pub fn rhb_state_diagram(format: &str) -> Option<String> {
+    let format = match format {
+        "mermaid" => DiagramFormat::Mermaid,
+        "graphviz" | "dot" => DiagramFormat::Graphviz,
+        _ => return None,
+    };
+    Some(game::state_diagram(format))
+}
+
+// RedHatBoy が最近受け取ったイベントと、状態・位置・速度の変化を JSON で返す
+// 不具合の報告を受けたときに、ブラウザのコンソールから呼び出して原因を調べる
+#[wasm_bindgen]
+pub fn rhb_history_json() -> Option<String> {
+    game::history_json()
+}
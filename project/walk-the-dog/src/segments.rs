@@ -1,30 +1,97 @@
 use std::rc::Rc;
 
-use web_sys::HtmlImageElement;
+use rand::{thread_rng, Rng};
 
 use crate::{
-    engine::renderer::{sprite::Sprite, Point, Rect},
+    engine::renderer::{sprite::Sprite, ImageElement, Point},
     game::{
-        bounding_box::BoundingBox,
-        objects::{platform::Platform, stone::Stone, Obstacle},
+        motion::Motion,
+        objects::{
+            enemy::{Behaviour, Enemy, EnemyKind},
+            item::{Item, ItemKind},
+            platform::Platform,
+            stone::Stone,
+            Collectible, CollisionBehaviour, GameObject, Obstacle,
+        },
+        power_up::PowerUp,
     },
 };
 
 // stone の y 座標
-const STONE_ON_GROUND: i16 = 545;
-const STONE_ON_LOW_PLATFORM: i16 = 366;
-const STONE_ON_HIGH_PLATFORM: i16 = 321;
+const STONE_ON_GROUND: i16 = 482;
+const STONE_ON_LOW_PLATFORM: i16 = 302;
+const STONE_ON_HIGH_PLATFORM: i16 = 257;
 
 // platform の y 座標
-const LOW_PLATFORM: i16 = 420;
-const HIGH_PLATFORM: i16 = 375;
+const LOW_PLATFORM: i16 = 356;
+const HIGH_PLATFORM: i16 = 311;
+// 頭上の梁の y 座標（走っていると頭がぶつかり、スライディングするとくぐれる高さ）
+const OVERHEAD_BEAM: i16 = 373;
 
-const FLOATING_PLATFORM_CELLS: [&str; 3] = ["13.png", "14.png", "15.png"];
-const FLOATING_PLATFORM_BOUNDING_BOX: [Rect; 3] = [
-    Rect::new_from_x_y(0, 0, 60, 54),
-    Rect::new_from_x_y(60, 0, 384 - (60 * 2), 93),
-    Rect::new_from_x_y(384 - 60, 0, 60, 54),
-];
+// 地面の y 座標（地面のタイルの上端）
+const GROUND: i16 = 536;
+
+// 地面を構成するセル（左側の崖・中央・右側の崖）
+const GROUND_LEFT_CELL: &str = "1.png";
+const GROUND_MIDDLE_CELL: &str = "2.png";
+const GROUND_RIGHT_CELL: &str = "7.png";
+
+// 頭上の梁の長さ（タイル数）
+const BEAM_LENGTH: usize = 2;
+
+// 1 つのセグメントの地面の長さ（タイル数）
+const SEGMENT_LENGTH: usize = 6;
+
+// 浮島を構成するセル（左端・中央・右端）
+const FLOATING_PLATFORM_LEFT_CELL: &str = "13.png";
+const FLOATING_PLATFORM_MIDDLE_CELL: &str = "14.png";
+const FLOATING_PLATFORM_RIGHT_CELL: &str = "15.png";
+
+// アイテムを platform や地面からどれだけ上に置くか
+const ITEM_ABOVE_PLATFORM: i16 = 60;
+const ITEM_ABOVE_PIT: i16 = 160;
+// 並べたアイテムの間隔
+const ITEM_SPACING: i16 = 48;
+
+// 浮島の長さ（タイル数）
+const SHORT_PLATFORM_LENGTH: usize = 3;
+const BRIDGE_LENGTH: usize = 4;
+
+// 地面の端の形状
+#[derive(Clone, Copy, PartialEq)]
+enum GroundEdge {
+    // 隣のセグメントの地面とつながっている
+    Continuous,
+    // 穴に面した崖になっている
+    Cliff,
+}
+
+// 1 つのセグメントに配置されるゲームオブジェクト
+pub struct Segment {
+    pub obstacles: Vec<Box<dyn Obstacle>>,
+    pub collectibles: Vec<Box<dyn Collectible>>,
+    pub enemies: Vec<Enemy>,
+}
+
+impl Segment {
+    pub fn new(obstacles: Vec<Box<dyn Obstacle>>) -> Self {
+        Self {
+            obstacles,
+            collectibles: vec![],
+            enemies: vec![],
+        }
+    }
+
+    fn with_collectibles(mut self, collectibles: Vec<Box<dyn Collectible>>) -> Self {
+        self.collectibles = collectibles;
+        self
+    }
+
+    fn with_enemies(mut self, enemies: Vec<Enemy>) -> Self {
+        self.enemies = enemies;
+        self
+    }
+}
 
 pub fn rightmost(obstacle_list: &Vec<Box<dyn Obstacle>>) -> i16 {
     obstacle_list
@@ -34,25 +101,98 @@ pub fn rightmost(obstacle_list: &Vec<Box<dyn Obstacle>>) -> i16 {
         .unwrap_or(0)
 }
 
-pub fn create_floating_platform(sprite: Rc<Sprite>, position: Point) -> Platform {
-    Platform::new(
-        sprite,
-        position,
-        &FLOATING_PLATFORM_CELLS,
-        BoundingBox::new(FLOATING_PLATFORM_BOUNDING_BOX.to_vec()),
-    )
+// length タイル分の長さの浮島を作成する
+pub fn create_floating_platform(sprite: Rc<Sprite>, position: Point, length: usize) -> Platform {
+    let cells = floating_platform_cells(length);
+
+    Platform::new(sprite, position, &cells)
+}
+
+// 左端・中央（length - 2 個）・右端のセル名を並べる
+// 両端のセルは必ず必要なので、length が 2 未満の場合は 2 として扱う
+fn floating_platform_cells(length: usize) -> Vec<&'static str> {
+    let middle_count = length.max(2) - 2;
+
+    std::iter::once(FLOATING_PLATFORM_LEFT_CELL)
+        .chain(std::iter::repeat_n(
+            FLOATING_PLATFORM_MIDDLE_CELL,
+            middle_count,
+        ))
+        .chain(std::iter::once(FLOATING_PLATFORM_RIGHT_CELL))
+        .collect()
+}
+
+// length タイル分の長さの地面を作成する
+// 地面は動かない Solid の足場で、上から触れると着地し、穴に落ちて側面にぶつかると押し戻される
+fn create_ground(
+    sprite: Rc<Sprite>,
+    x: i16,
+    length: usize,
+    left_edge: GroundEdge,
+    right_edge: GroundEdge,
+) -> Platform {
+    let cells = ground_cells(length, left_edge, right_edge);
+
+    Platform::new(sprite, Point { x, y: GROUND }, &cells).with_behaviour(CollisionBehaviour::Solid)
+}
+
+// 地面のセル名を並べる
+// 崖になっている端だけ崖のセルを使い、それ以外は中央のセルを使う
+fn ground_cells(length: usize, left_edge: GroundEdge, right_edge: GroundEdge) -> Vec<&'static str> {
+    let mut cells = vec![GROUND_MIDDLE_CELL; length.max(1)];
+    if left_edge == GroundEdge::Cliff {
+        cells[0] = GROUND_LEFT_CELL;
+    }
+    if right_edge == GroundEdge::Cliff {
+        let last = cells.len() - 1;
+        cells[last] = GROUND_RIGHT_CELL;
+    }
+    cells
+}
+
+// x から右に count 個のコインを並べる
+fn coin_row(items: Rc<Sprite>, x: i16, y: i16, count: i16) -> Vec<Box<dyn Collectible>> {
+    (0..count)
+        .map(|i| -> Box<dyn Collectible> {
+            Box::new(Item::new(
+                items.clone(),
+                ItemKind::Coin,
+                Point {
+                    x: x + i * ITEM_SPACING,
+                    y,
+                },
+            ))
+        })
+        .collect()
+}
+
+// ランダムな種類のパワーアップを position に置く
+fn random_power_up(items: Rc<Sprite>, position: Point) -> Box<dyn Collectible> {
+    let power_up = PowerUp::ALL[thread_rng().gen_range(0..PowerUp::ALL.len())];
+    Box::new(Item::new(items, ItemKind::PowerUp(power_up), position))
+}
+
+// 並べたセルの幅の合計
+fn cells_width(sprite: &Sprite, cells: &[&str]) -> i16 {
+    cells
+        .iter()
+        .map(|name| sprite.cell(name).expect("Error: Cell not found").width())
+        .sum()
 }
 
 pub fn two_stone_and_low_platform(
-    stone_image: HtmlImageElement,
+    stone_image: ImageElement,
     sprite: Rc<Sprite>,
+    items: Rc<Sprite>,
     offset_x: i16,
-) -> Vec<Box<dyn Obstacle>> {
+) -> Segment {
     const FIRST_STONE_X: i16 = 230;
     const SECOND_STONE_X: i16 = 450;
     const FIRST_PLATFORM: i16 = 300;
+    const COINS_X: i16 = 320;
+    const COIN_COUNT: i16 = 3;
 
-    vec![
+    Segment::new(vec![
         Box::new(Stone::new(
             stone_image.clone(),
             Point {
@@ -67,25 +207,45 @@ pub fn two_stone_and_low_platform(
                 y: STONE_ON_LOW_PLATFORM,
             },
         )),
-        Box::new(create_floating_platform(
+        // 低い足場には、下から跳び上がって乗ることができる
+        Box::new(
+            create_floating_platform(
+                sprite.clone(),
+                Point {
+                    x: offset_x + FIRST_PLATFORM,
+                    y: LOW_PLATFORM,
+                },
+                SHORT_PLATFORM_LENGTH,
+            )
+            .with_behaviour(CollisionBehaviour::OneWay),
+        ),
+        Box::new(create_ground(
             sprite,
-            Point {
-                x: offset_x + FIRST_PLATFORM,
-                y: LOW_PLATFORM,
-            },
+            offset_x,
+            SEGMENT_LENGTH,
+            GroundEdge::Continuous,
+            GroundEdge::Continuous,
         )),
-    ]
+    ])
+    .with_collectibles(coin_row(
+        items,
+        offset_x + COINS_X,
+        LOW_PLATFORM - ITEM_ABOVE_PLATFORM,
+        COIN_COUNT,
+    ))
 }
 
 pub fn stone_and_high_platform(
-    stone_image: HtmlImageElement,
+    stone_image: ImageElement,
     sprite: Rc<Sprite>,
+    items: Rc<Sprite>,
     offset_x: i16,
-) -> Vec<Box<dyn Obstacle>> {
+) -> Segment {
     const STONE_X: i16 = 330;
     const PLATFORM_X: i16 = 300;
+    const POWER_UP_X: i16 = 480;
 
-    vec![
+    Segment::new(vec![
         Box::new(Stone::new(
             stone_image,
             Point {
@@ -94,11 +254,251 @@ pub fn stone_and_high_platform(
             },
         )),
         Box::new(create_floating_platform(
-            sprite,
+            sprite.clone(),
             Point {
                 x: offset_x + PLATFORM_X,
                 y: HIGH_PLATFORM,
             },
+            SHORT_PLATFORM_LENGTH,
         )),
-    ]
+        Box::new(create_ground(
+            sprite,
+            offset_x,
+            SEGMENT_LENGTH,
+            GroundEdge::Continuous,
+            GroundEdge::Continuous,
+        )),
+    ])
+    .with_collectibles(vec![random_power_up(
+        items,
+        Point {
+            x: offset_x + POWER_UP_X,
+            y: HIGH_PLATFORM - ITEM_ABOVE_PLATFORM,
+        },
+    )])
+}
+
+pub fn pit(sprite: Rc<Sprite>, items: Rc<Sprite>, offset_x: i16) -> Segment {
+    const FIRST_GROUND_LENGTH: usize = 2;
+    const PIT_LENGTH: usize = 1;
+    const COINS_X: i16 = 272;
+    const COIN_COUNT: i16 = 3;
+
+    Segment::new(ground_around_pit(
+        sprite,
+        offset_x,
+        FIRST_GROUND_LENGTH,
+        PIT_LENGTH,
+    ))
+    .with_collectibles(coin_row(
+        items,
+        offset_x + COINS_X,
+        GROUND - ITEM_ABOVE_PIT,
+        COIN_COUNT,
+    ))
+}
+
+pub fn bridge_over_pit(sprite: Rc<Sprite>, items: Rc<Sprite>, offset_x: i16) -> Segment {
+    const FIRST_GROUND_LENGTH: usize = 1;
+    const PIT_LENGTH: usize = 4;
+    const BRIDGE_X: i16 = 192;
+    const GEM_X: i16 = 432;
+
+    let mut obstacles: Vec<Box<dyn Obstacle>> = vec![Box::new(create_floating_platform(
+        sprite.clone(),
+        Point {
+            x: offset_x + BRIDGE_X,
+            y: LOW_PLATFORM,
+        },
+        BRIDGE_LENGTH,
+    ))];
+    obstacles.append(&mut ground_around_pit(
+        sprite,
+        offset_x,
+        FIRST_GROUND_LENGTH,
+        PIT_LENGTH,
+    ));
+
+    Segment::new(obstacles).with_collectibles(vec![Box::new(Item::new(
+        items,
+        ItemKind::Gem,
+        Point {
+            x: offset_x + GEM_X,
+            y: LOW_PLATFORM - ITEM_ABOVE_PLATFORM,
+        },
+    ))])
+}
+
+pub fn moving_bridge_over_pit(sprite: Rc<Sprite>, offset_x: i16) -> Segment {
+    const FIRST_GROUND_LENGTH: usize = 1;
+    const PIT_LENGTH: usize = 4;
+    const PLATFORM_LENGTH: usize = 2;
+    const PLATFORM_X: i16 = 256;
+    const PLATFORM_MOTION: Motion = Motion::Oscillate {
+        amplitude: Point { x: 128, y: 0 },
+        period: 240,
+    };
+
+    let mut obstacles: Vec<Box<dyn Obstacle>> = vec![Box::new(
+        create_floating_platform(
+            sprite.clone(),
+            Point {
+                x: offset_x + PLATFORM_X,
+                y: LOW_PLATFORM,
+            },
+            PLATFORM_LENGTH,
+        )
+        .with_motion(PLATFORM_MOTION)
+        // 動く足場に下や横から飛び乗れるように、上面以外は通り抜けられるようにする
+        .with_behaviour(CollisionBehaviour::OneWay),
+    )];
+    obstacles.append(&mut ground_around_pit(
+        sprite,
+        offset_x,
+        FIRST_GROUND_LENGTH,
+        PIT_LENGTH,
+    ));
+    Segment::new(obstacles)
+}
+
+pub fn bobbing_stone(
+    stone_image: ImageElement,
+    sprite: Rc<Sprite>,
+    items: Rc<Sprite>,
+    offset_x: i16,
+) -> Segment {
+    const STONE_X: i16 = 400;
+    const POWER_UP_X: i16 = 200;
+    // 地面とプレイヤーの頭上の間を上下する
+    const BOB_HEIGHT: i16 = 64;
+    const STONE_MOTION: Motion = Motion::Oscillate {
+        amplitude: Point {
+            x: 0,
+            y: BOB_HEIGHT,
+        },
+        period: 120,
+    };
+
+    Segment::new(vec![
+        Box::new(
+            Stone::new(
+                stone_image,
+                Point {
+                    x: offset_x + STONE_X,
+                    y: STONE_ON_GROUND - BOB_HEIGHT,
+                },
+            )
+            .with_motion(STONE_MOTION),
+        ),
+        Box::new(create_ground(
+            sprite,
+            offset_x,
+            SEGMENT_LENGTH,
+            GroundEdge::Continuous,
+            GroundEdge::Continuous,
+        )),
+    ])
+    .with_collectibles(vec![random_power_up(
+        items,
+        Point {
+            x: offset_x + POWER_UP_X,
+            y: GROUND - ITEM_ABOVE_PLATFORM,
+        },
+    )])
+}
+
+pub fn overhead_beam(sprite: Rc<Sprite>, offset_x: i16) -> Segment {
+    const BEAM_X: i16 = 320;
+
+    Segment::new(vec![
+        Box::new(create_floating_platform(
+            sprite.clone(),
+            Point {
+                x: offset_x + BEAM_X,
+                y: OVERHEAD_BEAM,
+            },
+            BEAM_LENGTH,
+        )),
+        Box::new(create_ground(
+            sprite,
+            offset_x,
+            SEGMENT_LENGTH,
+            GroundEdge::Continuous,
+            GroundEdge::Continuous,
+        )),
+    ])
+}
+
+pub fn patrolling_slime(sprite: Rc<Sprite>, enemies: Rc<Sprite>, offset_x: i16) -> Segment {
+    const SLIME_X: i16 = 300;
+    const SLIME_HEIGHT: i16 = 32;
+    const PATROL_RANGE: i16 = 96;
+
+    Segment::new(vec![Box::new(create_ground(
+        sprite,
+        offset_x,
+        SEGMENT_LENGTH,
+        GroundEdge::Continuous,
+        GroundEdge::Continuous,
+    ))])
+    .with_enemies(vec![Enemy::new(
+        enemies,
+        EnemyKind::Slime,
+        Behaviour::Patrol {
+            range: PATROL_RANGE,
+        },
+        Point {
+            x: offset_x + SLIME_X,
+            y: GROUND - SLIME_HEIGHT,
+        },
+    )])
+}
+
+pub fn swooping_bat(sprite: Rc<Sprite>, enemies: Rc<Sprite>, offset_x: i16) -> Segment {
+    const BAT_X: i16 = 500;
+    const BAT_Y: i16 = 300;
+    const SIGHT: i16 = 300;
+
+    Segment::new(vec![Box::new(create_ground(
+        sprite,
+        offset_x,
+        SEGMENT_LENGTH,
+        GroundEdge::Continuous,
+        GroundEdge::Continuous,
+    ))])
+    .with_enemies(vec![Enemy::new(
+        enemies,
+        EnemyKind::Bat,
+        Behaviour::Homing { sight: SIGHT },
+        Point {
+            x: offset_x + BAT_X,
+            y: BAT_Y,
+        },
+    )])
+}
+
+// first_ground_length タイル分の地面、pit_length タイル分の穴、残りの地面を並べる
+fn ground_around_pit(
+    sprite: Rc<Sprite>,
+    offset_x: i16,
+    first_ground_length: usize,
+    pit_length: usize,
+) -> Vec<Box<dyn Obstacle>> {
+    let first_ground = create_ground(
+        sprite.clone(),
+        offset_x,
+        first_ground_length,
+        GroundEdge::Continuous,
+        GroundEdge::Cliff,
+    );
+    let pit_width = cells_width(&sprite, &[GROUND_MIDDLE_CELL]) * pit_length as i16;
+    let second_ground = create_ground(
+        sprite,
+        first_ground.bounding_box().right() + pit_width,
+        SEGMENT_LENGTH - first_ground_length - pit_length,
+        GroundEdge::Cliff,
+        GroundEdge::Continuous,
+    );
+
+    vec![Box::new(first_ground), Box::new(second_ground)]
 }
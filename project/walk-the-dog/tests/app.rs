@@ -1,35 +1,184 @@
-use wasm_bindgen_test::{wasm_bindgen_test_configure, wasm_bindgen_test};
-use futures::prelude::*;
-use wasm_bindgen::JsValue;
+// ブラウザ上で実行する結合テスト
+// `wasm-pack test --headless --firefox`（または --chrome）で実行する
+// テストランナーのサーバーは crate のディレクトリのファイルも配信するので、static/ の画像と JSON をそのまま読み込む
+#![cfg(target_arch = "wasm32")]
+
+use rust_webpack_template::{
+    engine::{
+        key_state::KeyState,
+        renderer::{image, Renderer},
+        Game, GameLoop,
+    },
+    game::{self, WalkTheDog},
+};
+use serde_json::Value;
+use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
 
 wasm_bindgen_test_configure!(run_in_browser);
 
+// ゲームが読み込む画像と JSON の場所（ゲームは相対パスで読み込むので、<base> で static/ を指す）
+const ASSETS: &str = "/static/";
+const CANVAS_SIZE: u32 = 600;
+
+// 最初のセグメントの石にぶつかるまでに十分なフレーム数
+const FRAMES_TO_REACH_STONE: usize = 300;
+
+// ゲームが描画する canvas と、素材を読み込む <base> をテスト用のページに用意する
+fn prepare_page() -> HtmlCanvasElement {
+    let document = web_sys::window().unwrap().document().unwrap();
+    if let Some(canvas) = document.get_element_by_id("canvas") {
+        return canvas.dyn_into().unwrap();
+    }
+
+    let base = document.create_element("base").unwrap();
+    base.set_attribute("href", ASSETS).unwrap();
+    document.head().unwrap().append_child(&base).unwrap();
+
+    let canvas: HtmlCanvasElement = document
+        .create_element("canvas")
+        .unwrap()
+        .dyn_into()
+        .unwrap();
+    canvas.set_id("canvas");
+    canvas.set_width(CANVAS_SIZE);
+    canvas.set_height(CANVAS_SIZE);
+    document.body().unwrap().append_child(&canvas).unwrap();
+    canvas
+}
+
+async fn sleep(milliseconds: i32) {
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+        web_sys::window()
+            .unwrap()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, milliseconds)
+            .unwrap();
+    });
+    JsFuture::from(promise).await.unwrap();
+}
+
+async fn loaded_game() -> Box<dyn Game> {
+    prepare_page();
+    WalkTheDog::new()
+        .initialize()
+        .await
+        .expect("Error loading assets from the test server")
+}
+
+// ゲームループの代わりに、frames フレーム分 update と draw を繰り返す
+fn run_frames(game: &mut Box<dyn Game>, keystate: &KeyState, frames: usize) {
+    let renderer = Renderer::new().expect("Error creating renderer");
+    for _ in 0..frames {
+        game.update(keystate);
+        game.draw(&renderer);
+    }
+}
 
-// This runs a unit test in native Rust, so it can only use Rust APIs.
-#[test]
-fn rust_test() {
-    assert_eq!(1, 1);
+// 最後に作られた rhb の履歴の最後の項目
+fn last_history_entry() -> Value {
+    let json = game::history_json().expect("rhb should have been created");
+    let history: Value = serde_json::from_str(&json).unwrap();
+    history
+        .as_array()
+        .and_then(|entries| entries.last())
+        .cloned()
+        .expect("history should not be empty")
 }
 
+// 最後に作られた rhb の今の状態（最後のイベントの遷移後の状態。無視されたイベントなら遷移前の状態のまま）
+fn current_state() -> String {
+    let entry = last_history_entry();
+    entry["to"]
+        .as_str()
+        .or_else(|| entry["from"].as_str())
+        .expect("history entries should have a state")
+        .to_string()
+}
+
+fn last_tick() -> u64 {
+    last_history_entry()["tick"]
+        .as_u64()
+        .expect("history entries should have a tick")
+}
 
-// This runs a unit test in the browser, so it can use browser APIs.
 #[wasm_bindgen_test]
-fn web_test() {
-    assert_eq!(1, 1);
+async fn load_image_reports_its_size() {
+    prepare_page();
+
+    let stone = image::load_image("Stone.png").await.unwrap();
+    assert_eq!((stone.width(), stone.height()), (90, 54));
 }
 
+#[wasm_bindgen_test]
+async fn load_image_fails_for_missing_files() {
+    prepare_page();
 
-// This runs a unit test in the browser, and in addition it supports asynchronous Future APIs.
-// #[wasm_bindgen_test(async)]
-// fn async_test() -> impl Future<Item = (), Error = JsValue> {
-//     // Creates a JavaScript Promise which will asynchronously resolve with the value 42.
-//     let promise = js_sys::Promise::resolve(&JsValue::from(42));
+    assert!(image::load_image("missing.png").await.is_err());
+}
+
+#[wasm_bindgen_test]
+async fn drawing_paints_the_canvas() {
+    let canvas = prepare_page();
+    let mut game = loaded_game().await;
 
-//     // Converts that Promise into a Future.
-//     // The unit test will wait for the Future to resolve.
-//     JsFuture::from(promise)
-//         .map(|x| {
-//             assert_eq!(x, 42);
-//         })
-// }
+    run_frames(&mut game, &KeyState::new(), 10);
+
+    let context: CanvasRenderingContext2d = canvas
+        .get_context("2d")
+        .unwrap()
+        .unwrap()
+        .dyn_into()
+        .unwrap();
+    let pixels = context
+        .get_image_data(0., 0., CANVAS_SIZE as f64, CANVAS_SIZE as f64)
+        .unwrap()
+        .data();
+    // 4 バイトごとの最後のバイトが透明度
+    assert!(pixels.iter().skip(3).step_by(4).any(|alpha| *alpha > 0));
+}
+
+#[wasm_bindgen_test]
+async fn standing_still_keeps_the_player_alive() {
+    let mut game = loaded_game().await;
+
+    run_frames(&mut game, &KeyState::new(), FRAMES_TO_REACH_STONE);
+
+    assert_eq!(current_state(), "Idle");
+}
+
+#[wasm_bindgen_test]
+async fn running_into_a_stone_knocks_the_player_out() {
+    let mut game = loaded_game().await;
+    let mut keystate = KeyState::new();
+    keystate.set_pressed("ArrowRight");
+
+    // ジャンプせずに走り続けると、最初のセグメントの地面の上の石にぶつかる
+    run_frames(&mut game, &keystate, FRAMES_TO_REACH_STONE);
+
+    assert_eq!(current_state(), "KnockedOut");
+}
+
+// ゲームループは止めてから終わり、canvas も元の大きさに戻して、後のテストに影響しないようにする
+#[wasm_bindgen_test]
+async fn game_loop_updates_on_animation_frames_until_stopped() {
+    let canvas = prepare_page();
+
+    let game_loop = GameLoop::start(WalkTheDog::new())
+        .await
+        .expect("Error starting game loop");
+    sleep(500).await;
+    game_loop.stop().expect("Error stopping game loop");
+
+    let tick = last_tick();
+    assert!(tick > 0, "the loop should have called update");
+
+    sleep(200).await;
+    assert_eq!(last_tick(), tick, "the loop should have stopped");
+
+    canvas.style().remove_property("width").unwrap();
+    canvas.style().remove_property("height").unwrap();
+    canvas.set_width(CANVAS_SIZE);
+    canvas.set_height(CANVAS_SIZE);
+}
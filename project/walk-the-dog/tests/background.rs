@@ -0,0 +1,233 @@
+// 背景のレイヤーのテスト
+// 画像は読み込まず、大きさだけを持つ画像で作る（ネイティブでしか作れないので、ブラウザでのテストには含めない）
+#![cfg(not(target_arch = "wasm32"))]
+
+use rust_webpack_template::{
+    engine::renderer::{ImageElement, Rect},
+    game::background::{Background, BackgroundConfig, Layer, LayerConfig, Tiling},
+};
+
+const IMAGE_WIDTH: u32 = 1000;
+const IMAGE_HEIGHT: u32 = 600;
+
+// いろいろな大きさの画面（画像より狭いもの、画像 2 枚より広いものを含む）
+const CANVAS_SIZES: [(i16, i16); 4] = [(300, 200), (600, 600), (1000, 600), (2500, 1500)];
+// 1 フレームにカメラが動く距離（画像の幅より速いものや、左向きのものを含む）
+const SPEEDS: [i16; 8] = [1, 3, 4, 17, 999, 1000, 2600, -7];
+
+fn layer_config(json: &str) -> LayerConfig {
+    serde_json::from_str(json).expect("layer config should parse")
+}
+
+fn layer(json: &str) -> Layer {
+    Layer::new(
+        &layer_config(json),
+        ImageElement::new(IMAGE_WIDTH, IMAGE_HEIGHT),
+    )
+}
+
+fn background(configs: &[&str], view: Rect) -> Background {
+    Background::new(configs.iter().map(|json| layer(json)).collect(), view)
+}
+
+fn view(x: i16, y: i16, (width, height): (i16, i16)) -> Rect {
+    Rect::new_from_x_y(x, y, width, height)
+}
+
+fn leftmost(layer: &Layer) -> i16 {
+    layer
+        .tiles(600, 600)
+        .iter()
+        .map(|tile| tile.x())
+        .min()
+        .unwrap()
+}
+
+// 並べた位置が隙間なく続き、0 から length までを覆っているか
+fn assert_covers(mut positions: Vec<i16>, size: i16, length: i16, context: &str) {
+    positions.sort();
+    positions.dedup();
+    assert!(positions[0] <= 0, "gap at the start: {}", context);
+    assert!(
+        positions[positions.len() - 1] + size >= length,
+        "gap at the end: {}",
+        context
+    );
+    positions.windows(2).for_each(|pair| {
+        assert_eq!(pair[0] + size, pair[1], "gap between tiles: {}", context);
+    });
+}
+
+#[test]
+fn config_file_parses_and_fills_in_defaults() {
+    let config: BackgroundConfig =
+        serde_json::from_str(include_str!("../static/background.json")).unwrap();
+    assert!(!config.layers.is_empty());
+
+    let layer = layer_config(r#"{ "image": "Mountains.png" }"#);
+    assert_eq!(layer.scroll_factor, 1.);
+    assert_eq!(layer.offset_y, 0);
+    assert!(layer.tiling == Tiling::Horizontal);
+}
+
+#[test]
+fn layers_move_by_their_scroll_factor() {
+    let size = (600, 600);
+    let mut background = background(
+        &[
+            r#"{ "image": "Sky.png", "scroll_factor": 0, "tiling": "single" }"#,
+            r#"{ "image": "Hills.png", "scroll_factor": 0.5, "tiling": "single" }"#,
+            r#"{ "image": "Grass.png", "scroll_factor": 1, "tiling": "single" }"#,
+        ],
+        view(0, 0, size),
+    );
+
+    // 1 フレームに 3 ピクセルずつ、10 フレーム動く
+    (1..=10).for_each(|frame| background.update(&view(frame * 3, 0, size)));
+
+    let moved: Vec<i16> = background.layers().iter().map(leftmost).collect();
+    assert_eq!(moved, vec![0, -15, -30]);
+}
+
+#[test]
+fn layers_keep_their_vertical_offset() {
+    let clouds = layer(r#"{ "image": "Clouds.png", "offset_y": 120 }"#);
+
+    assert!(clouds.tiles(600, 600).iter().all(|tile| tile.y() == 120));
+}
+
+#[test]
+fn horizontal_tiles_cover_the_canvas_at_any_speed_and_size() {
+    for size in CANVAS_SIZES {
+        for speed in SPEEDS {
+            for factor in ["0.25", "0.5", "1"] {
+                let config = format!(r#"{{ "image": "BG.png", "scroll_factor": {} }}"#, factor);
+                let mut background = background(&[&config], view(0, 0, size));
+
+                for frame in 1..=50 {
+                    background.update(&view(speed.saturating_mul(frame), 0, size));
+
+                    let tiles = background.layers()[0].tiles(size.0, size.1);
+                    let context = format!(
+                        "canvas {:?}, speed {}, factor {}, frame {}",
+                        size, speed, factor, frame
+                    );
+                    assert!(tiles.iter().all(|tile| tile.y() == 0), "{}", context);
+                    assert_covers(
+                        tiles.iter().map(|tile| tile.x()).collect(),
+                        IMAGE_WIDTH as i16,
+                        size.0,
+                        &context,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn tiles_wrap_when_running_left_and_back() {
+    let size = (600, 600);
+    let mut background = background(&[r#"{ "image": "BG.png" }"#], view(0, 0, size));
+
+    // 左に 3000 ピクセル進んでから、元の位置に戻る
+    let path = (0..=300)
+        .map(|frame| -10 * frame)
+        .chain((0..=300).rev().map(|frame| -10 * frame));
+    for x in path {
+        background.update(&view(x, 0, size));
+        let tiles = background.layers()[0].tiles(size.0, size.1);
+        assert_covers(
+            tiles.iter().map(|tile| tile.x()).collect(),
+            IMAGE_WIDTH as i16,
+            size.0,
+            &format!("camera at {}", x),
+        );
+    }
+
+    assert_eq!(leftmost(&background.layers()[0]), 0);
+}
+
+#[test]
+fn tiling_in_both_directions_covers_the_canvas() {
+    for size in CANVAS_SIZES {
+        for speed in SPEEDS {
+            let mut background = background(
+                &[r#"{ "image": "Pattern.png", "offset_y": 250, "tiling": "both" }"#],
+                view(0, 0, size),
+            );
+
+            for frame in 1..=20 {
+                let distance = speed.saturating_mul(frame);
+                background.update(&view(distance, distance / 2, size));
+
+                let tiles = background.layers()[0].tiles(size.0, size.1);
+                let context = format!("canvas {:?}, speed {}, frame {}", size, speed, frame);
+                assert_covers(
+                    tiles.iter().map(|tile| tile.x()).collect(),
+                    IMAGE_WIDTH as i16,
+                    size.0,
+                    &context,
+                );
+                assert_covers(
+                    tiles.iter().map(|tile| tile.y()).collect(),
+                    IMAGE_HEIGHT as i16,
+                    size.1,
+                    &context,
+                );
+                // 縦横の組み合わせがすべて並んでいる
+                let columns = tiles.iter().filter(|tile| tile.y() == tiles[0].y()).count();
+                let rows = tiles.iter().filter(|tile| tile.x() == tiles[0].x()).count();
+                assert_eq!(tiles.len(), columns * rows, "{}", context);
+            }
+        }
+    }
+}
+
+#[test]
+fn vertical_tiling_does_not_repeat_horizontally() {
+    let mut background = background(
+        &[r#"{ "image": "Waterfall.png", "tiling": "vertical" }"#],
+        view(0, 0, (600, 1500)),
+    );
+    background.update(&view(40, 700, (600, 1500)));
+
+    let tiles = background.layers()[0].tiles(600, 1500);
+    assert!(tiles.iter().all(|tile| tile.x() == -40));
+    assert_covers(
+        tiles.iter().map(|tile| tile.y()).collect(),
+        IMAGE_HEIGHT as i16,
+        1500,
+        "vertical",
+    );
+}
+
+#[test]
+fn single_layers_are_not_repeated() {
+    let size = (600, 600);
+    let mut background = background(
+        &[r#"{ "image": "Sun.png", "tiling": "single" }"#],
+        view(0, 0, size),
+    );
+    background.update(&view(1500, 0, size));
+
+    let tiles = background.layers()[0].tiles(size.0, size.1);
+    assert_eq!(tiles.len(), 1);
+    assert_eq!(tiles[0].x(), -1500);
+}
+
+#[test]
+fn shifting_the_world_origin_does_not_move_layers() {
+    let size = (600, 600);
+    let mut background = background(
+        &[r#"{ "image": "BG.png", "scroll_factor": 0.5 }"#],
+        view(0, 0, size),
+    );
+    background.update(&view(100, 0, size));
+    let before = leftmost(&background.layers()[0]);
+
+    background.shift(-100);
+    background.update(&view(0, 0, size));
+
+    assert_eq!(leftmost(&background.layers()[0]), before);
+}
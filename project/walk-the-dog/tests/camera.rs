@@ -0,0 +1,129 @@
+// カメラの追従と画面の揺れのテスト
+use rust_webpack_template::engine::{
+    camera::Camera,
+    renderer::{Point, Rect},
+};
+
+const WIDTH: i16 = 600;
+const HEIGHT: i16 = 600;
+
+fn camera() -> Camera {
+    Camera::new(WIDTH, HEIGHT).with_dead_zone(Rect::new_from_x_y(100, 100, 200, 300))
+}
+
+fn origin(camera: &Camera) -> (i16, i16) {
+    let origin = camera.origin();
+    (origin.x, origin.y)
+}
+
+#[test]
+fn target_inside_the_dead_zone_does_not_move_the_camera() {
+    let mut camera = camera();
+    camera.update(Point { x: 150, y: 200 });
+    camera.update(Point { x: 290, y: 390 });
+
+    assert_eq!(origin(&camera), (0, 0));
+}
+
+#[test]
+fn camera_keeps_the_target_at_the_edge_of_the_dead_zone() {
+    let mut camera = camera();
+    camera.update(Point { x: 500, y: 50 });
+
+    // 右に 200、上に 50 はみ出した分だけ動く
+    assert_eq!(origin(&camera), (200, -50));
+    assert_eq!(camera.to_screen(Point { x: 500, y: 50 }).x, 300);
+}
+
+#[test]
+fn smoothing_approaches_the_target_over_several_frames() {
+    let mut camera = camera().with_smoothing(0.5);
+    let target = Point { x: 700, y: 200 };
+
+    camera.update(target);
+    assert_eq!(origin(&camera).0, 200);
+    camera.update(target);
+    assert_eq!(origin(&camera).0, 300);
+
+    (0..20).for_each(|_| camera.update(target));
+    assert_eq!(origin(&camera).0, 400);
+}
+
+#[test]
+fn vertical_limits_keep_the_camera_inside_the_world() {
+    let mut camera = camera().with_vertical_limits(0, HEIGHT);
+    camera.update(Point { x: 200, y: -500 });
+    assert_eq!(origin(&camera).1, 0);
+
+    camera.update(Point { x: 200, y: 2000 });
+    assert_eq!(origin(&camera).1, 0);
+}
+
+#[test]
+fn bounds_are_the_visible_part_of_the_world() {
+    let mut camera = camera();
+    camera.update(Point { x: 1300, y: 200 });
+
+    let bounds = camera.bounds();
+    assert_eq!((bounds.x(), bounds.right()), (1000, 1600));
+    assert!(camera.is_visible(&Rect::new_from_x_y(1550, 0, 100, 100)));
+    assert!(!camera.is_visible(&Rect::new_from_x_y(900, 0, 100, 100)));
+}
+
+#[test]
+fn shake_moves_the_origin_but_not_the_bounds_and_fades_out() {
+    let mut camera = camera();
+    camera.shake(8., 10);
+    assert!(camera.is_shaking());
+
+    let mut shaken = false;
+    for _ in 0..10 {
+        shaken |= origin(&camera) != (0, 0);
+        assert_eq!(camera.bounds().x(), 0);
+        camera.update(Point { x: 200, y: 200 });
+    }
+
+    assert!(shaken);
+    assert!(!camera.is_shaking());
+    assert_eq!(origin(&camera), (0, 0));
+}
+
+#[test]
+fn shift_keeps_the_same_view_of_the_world() {
+    let mut camera = camera();
+    camera.update(Point { x: 1300, y: 200 });
+    camera.shift(-1000);
+
+    assert_eq!(origin(&camera), (0, 0));
+}
+
+#[test]
+fn backtrack_limit_stops_the_camera_from_scrolling_far_back_left() {
+    let mut camera = camera().with_backtrack_limit(300);
+    camera.update(Point { x: 1300, y: 200 });
+    assert_eq!(origin(&camera).0, 1000);
+
+    // 左に戻るのは一番右に進んだ位置から 300 まで
+    camera.update(Point { x: 800, y: 200 });
+    assert_eq!(origin(&camera).0, 700);
+    camera.update(Point { x: 0, y: 200 });
+    assert_eq!(origin(&camera).0, 700);
+
+    // それより左にあるものはもう映らない
+    assert!(camera.is_left_behind(699));
+    assert!(!camera.is_left_behind(700));
+
+    camera.shift(-1000);
+    assert!(camera.is_left_behind(-301));
+    assert!(!camera.is_left_behind(-300));
+}
+
+#[test]
+fn without_a_backtrack_limit_nothing_is_left_behind() {
+    let mut camera = camera();
+    camera.update(Point { x: 1300, y: 200 });
+    camera.update(Point { x: 0, y: 200 });
+
+    assert_eq!(origin(&camera).0, -100);
+    assert!(!camera.is_left_behind(-10000));
+}
@@ -0,0 +1,74 @@
+// 矩形と当たり判定のテスト（ブラウザなしで実行できる）
+use rust_webpack_template::engine::{
+    collision::{contacts, Side},
+    renderer::{Point, Rect},
+};
+
+const STILL: Point = Point { x: 0, y: 0 };
+
+#[test]
+fn overlapping_rects_intersect() {
+    let a = Rect::new_from_x_y(0, 0, 100, 100);
+    let b = Rect::new_from_x_y(50, 60, 100, 100);
+
+    let overlap = a.intersection(&b).expect("rects should intersect");
+    assert_eq!(
+        (overlap.x(), overlap.y(), overlap.w, overlap.h),
+        (50, 60, 50, 40)
+    );
+}
+
+#[test]
+fn touching_rects_do_not_intersect() {
+    let a = Rect::new_from_x_y(0, 0, 100, 100);
+    let b = Rect::new_from_x_y(100, 0, 100, 100);
+
+    assert!(!a.intersects(&b));
+    assert!(a.intersection(&b).is_none());
+}
+
+#[test]
+fn falling_onto_a_rect_touches_its_top() {
+    let floor = Rect::new_from_x_y(0, 100, 200, 50);
+    // 10 ピクセル落ちて、床に 5 ピクセルめり込んだ
+    let player = Rect::new_from_x_y(50, 55, 20, 50);
+
+    let found = contacts(&[player], Point { x: 0, y: 10 }, &[floor]);
+    let contact = found.first().expect("player should touch the floor");
+    assert!(contact.side == Side::Top);
+    assert_eq!(contact.push_out().y, -5);
+}
+
+#[test]
+fn running_into_a_wall_touches_its_side() {
+    let wall = Rect::new_from_x_y(100, 0, 50, 200);
+    let player = Rect::new_from_x_y(84, 50, 20, 50);
+
+    let found = contacts(&[player], Point { x: 8, y: 0 }, &[wall]);
+    let contact = found.first().expect("player should touch the wall");
+    assert!(contact.side == Side::Left);
+    assert_eq!(contact.push_out().x, -4);
+}
+
+#[test]
+fn fast_movement_does_not_tunnel_through_thin_rects() {
+    let platform = Rect::new_from_x_y(0, 100, 200, 10);
+    // 1 フレームで足場を通り抜けた位置にいる
+    let player = Rect::new_from_x_y(50, 120, 20, 20);
+
+    let found = contacts(&[player], Point { x: 0, y: 60 }, &[platform]);
+    let contact = found.first().expect("sweep should catch the platform");
+    assert!(contact.side == Side::Top);
+    assert!(contact.overlap.is_none());
+}
+
+#[test]
+fn moving_away_or_standing_apart_has_no_contact() {
+    let target = Rect::new_from_x_y(100, 100, 50, 50);
+    let apart = Rect::new_from_x_y(0, 0, 20, 20);
+    // 右側面に触れていた位置から離れていく
+    let leaving = Rect::new_from_x_y(155, 100, 20, 20);
+
+    assert!(contacts(&[apart], STILL, &[target.clone()]).is_empty());
+    assert!(contacts(&[leaving], Point { x: 5, y: 0 }, &[target]).is_empty());
+}
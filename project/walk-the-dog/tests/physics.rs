@@ -0,0 +1,104 @@
+// 物理パラメータの検証と、スプライトシートとの整合のテスト
+// スプライトシートは画像を読み込まず、JSON と大きさだけの画像で作る（ネイティブでしか作れないので、ブラウザでのテストには含めない）
+#![cfg(not(target_arch = "wasm32"))]
+
+use rust_webpack_template::{
+    engine::renderer::{
+        sprite::{Sprite, SpriteSheet},
+        ImageElement,
+    },
+    game::{physics::PhysicsConfig, rhb::RedHatBoy},
+};
+
+// static/rhb.json の当たり判定の下端
+const HITBOX_BOTTOM: i16 = 121;
+
+fn config(json: &str) -> PhysicsConfig {
+    serde_json::from_str(json).expect("physics config should parse")
+}
+
+fn rhb_sprite() -> Sprite {
+    let sheet: SpriteSheet = serde_json::from_str(include_str!("../static/rhb.json"))
+        .expect("sprite sheet should parse");
+    Sprite::new(sheet, ImageElement::new(1, 1))
+}
+
+#[test]
+fn config_file_is_valid_and_fits_the_sprite_sheet() {
+    let physics = config(include_str!("../static/physics.json"));
+    physics.validate().unwrap();
+
+    let physics = RedHatBoy::fit_physics(&rhb_sprite(), physics).unwrap();
+    assert_eq!(physics.player_height, HITBOX_BOTTOM);
+}
+
+#[test]
+fn out_of_range_values_are_rejected() {
+    for json in [
+        r#"{ "running_speed": -1 }"#,
+        r#"{ "gravity": 0 }"#,
+        r#"{ "terminal_velocity": 0 }"#,
+        r#"{ "jump_speed": 10 }"#,
+        r#"{ "jump_cut_speed": 3 }"#,
+        r#"{ "bounce_speed": 0 }"#,
+        r#"{ "coyote_frames": 200 }"#,
+        r#"{ "jump_buffer_frames": 200 }"#,
+        r#"{ "frame_counts": { "jumping": 0 } }"#,
+        r#"{ "frame_counts": { "idle": 0 } }"#,
+    ] {
+        assert!(
+            config(json).validate().is_err(),
+            "{} should be rejected",
+            json
+        );
+    }
+}
+
+#[test]
+fn player_height_comes_from_the_sprite_sheet() {
+    // physics.json に書かれていても使わない
+    let physics = config(r#"{ "player_height": 90 }"#);
+
+    let physics = RedHatBoy::fit_physics(&rhb_sprite(), physics).unwrap();
+    assert_eq!(physics.player_height, HITBOX_BOTTOM);
+}
+
+#[test]
+fn frame_counts_beyond_the_sprite_sheet_are_rejected() {
+    // Jump の画像は 12 枚なので、36 フレームまでしか表示できない
+    let fits = config(r#"{ "frame_counts": { "jumping": 36 } }"#);
+    assert!(RedHatBoy::fit_physics(&rhb_sprite(), fits).is_ok());
+
+    let too_long = config(r#"{ "frame_counts": { "jumping": 37 } }"#);
+    assert!(RedHatBoy::fit_physics(&rhb_sprite(), too_long).is_err());
+}
+
+#[cfg(feature = "physics_debug")]
+#[test]
+fn panel_keeps_values_in_range() {
+    use rust_webpack_template::{engine::key_state::KeyState, game::physics::panel::PhysicsPanel};
+
+    let mut panel = PhysicsPanel::default();
+    let mut physics = PhysicsConfig::default();
+    let mut keystate = KeyState::new();
+
+    // 押してから離すと 1 回だけ反応する
+    let mut press = |panel: &mut PhysicsPanel, physics: &mut PhysicsConfig, key: &str| {
+        keystate.set_pressed(key);
+        panel.update(&keystate, physics);
+        keystate.set_released(key);
+        panel.update(&keystate, physics);
+    };
+
+    // パネルを開き、最初の項目（running_speed）を下限より小さくしようとする
+    press(&mut panel, &mut physics, "KeyP");
+    (0..100).for_each(|_| press(&mut panel, &mut physics, "KeyA"));
+    assert_eq!(physics.running_speed, 0.);
+
+    // すべての項目を上限より大きくしようとしても、範囲内に収まる
+    for _ in 0..8 {
+        (0..500).for_each(|_| press(&mut panel, &mut physics, "KeyD"));
+        press(&mut panel, &mut physics, "KeyS");
+    }
+    physics.validate().unwrap();
+}
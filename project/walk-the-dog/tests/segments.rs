@@ -0,0 +1,155 @@
+// セグメントの配置のテスト
+// 画像は読み込まず、スプライトシートの JSON と画像の大きさだけでゲームオブジェクトを作る
+// （大きさだけの画像はネイティブでしか作れないので、ブラウザでのテストには含めない）
+#![cfg(not(target_arch = "wasm32"))]
+use std::rc::Rc;
+
+use rust_webpack_template::{
+    engine::{
+        key_state::KeyState,
+        renderer::{
+            sprite::{Sprite, SpriteSheet},
+            ImageElement,
+        },
+    },
+    game::objects::GameObject,
+    segments::{self, rightmost, Segment},
+};
+
+// 地面のタイルの上端
+const GROUND: i16 = 536;
+const OFFSET: i16 = 1000;
+
+fn sprite(json: &str, width: u32, height: u32) -> Rc<Sprite> {
+    let sheet: SpriteSheet = serde_json::from_str(json).expect("sprite sheet should parse");
+    Rc::new(Sprite::new(sheet, ImageElement::new(width, height)))
+}
+
+fn all_segments(offset_x: i16) -> Vec<(&'static str, Segment)> {
+    let tiles = sprite(include_str!("../static/tiles.json"), 650, 485);
+    let items = sprite(include_str!("../static/items.json"), 272, 136);
+    let enemies = sprite(include_str!("../static/enemies.json"), 250, 68);
+    let stone = ImageElement::new(90, 54);
+
+    vec![
+        (
+            "two_stone_and_low_platform",
+            segments::two_stone_and_low_platform(
+                stone.clone(),
+                tiles.clone(),
+                items.clone(),
+                offset_x,
+            ),
+        ),
+        (
+            "stone_and_high_platform",
+            segments::stone_and_high_platform(
+                stone.clone(),
+                tiles.clone(),
+                items.clone(),
+                offset_x,
+            ),
+        ),
+        ("pit", segments::pit(tiles.clone(), items.clone(), offset_x)),
+        (
+            "bridge_over_pit",
+            segments::bridge_over_pit(tiles.clone(), items.clone(), offset_x),
+        ),
+        (
+            "moving_bridge_over_pit",
+            segments::moving_bridge_over_pit(tiles.clone(), offset_x),
+        ),
+        (
+            "bobbing_stone",
+            segments::bobbing_stone(stone, tiles.clone(), items, offset_x),
+        ),
+        (
+            "overhead_beam",
+            segments::overhead_beam(tiles.clone(), offset_x),
+        ),
+        (
+            "patrolling_slime",
+            segments::patrolling_slime(tiles.clone(), enemies.clone(), offset_x),
+        ),
+        (
+            "swooping_bat",
+            segments::swooping_bat(tiles, enemies, offset_x),
+        ),
+    ]
+}
+
+// 前のセグメントから走ってきた rhb が落ちないように、どのセグメントも地面から始まり地面で終わる
+#[test]
+fn every_segment_starts_and_ends_on_ground() {
+    for (name, segment) in all_segments(OFFSET) {
+        let ground: Vec<_> = segment
+            .obstacles
+            .iter()
+            .flat_map(|obstacle| obstacle.bounding_box().rects().to_vec())
+            .filter(|rect| rect.y() == GROUND)
+            .collect();
+        let end = rightmost(&segment.obstacles);
+
+        assert!(
+            ground.iter().any(|rect| rect.x() == OFFSET),
+            "{} does not start on ground",
+            name
+        );
+        assert!(
+            ground.iter().any(|rect| rect.right() == end),
+            "{} does not end on ground",
+            name
+        );
+    }
+}
+
+#[test]
+fn every_segment_is_placed_after_its_offset() {
+    for (name, segment) in all_segments(OFFSET) {
+        let end = rightmost(&segment.obstacles);
+        assert!(end > OFFSET, "{} has no width", name);
+
+        let boxes = segment
+            .obstacles
+            .iter()
+            .map(|obstacle| obstacle.bounding_box())
+            .chain(segment.collectibles.iter().map(|item| item.bounding_box()))
+            .chain(segment.enemies.iter().map(|enemy| enemy.bounding_box()));
+        boxes
+            .flat_map(|bounding_box| bounding_box.rects().to_vec())
+            .for_each(|rect| {
+                assert!(
+                    rect.x() >= OFFSET && rect.right() <= end,
+                    "{} places an object outside {}..{}",
+                    name,
+                    OFFSET,
+                    end
+                );
+            });
+    }
+}
+
+// ワールドの原点を移しても、セグメントの形は変わらない
+#[test]
+fn segments_move_with_the_world_origin() {
+    for (name, mut segment) in all_segments(OFFSET) {
+        let end = rightmost(&segment.obstacles);
+        segment
+            .obstacles
+            .iter_mut()
+            .for_each(|obstacle| obstacle.shift(-OFFSET));
+
+        assert_eq!(rightmost(&segment.obstacles), end - OFFSET, "{}", name);
+    }
+}
+
+#[test]
+fn key_state_tracks_pressed_keys() {
+    let mut keystate = KeyState::new();
+    keystate.set_pressed("Space");
+    keystate.set_pressed("ArrowDown");
+    keystate.set_released("Space");
+
+    assert!(!keystate.is_pressed("Space"));
+    assert!(keystate.is_pressed("ArrowDown"));
+}
@@ -0,0 +1,108 @@
+// ゲームの画面をウィンドウに合わせて拡大・縮小するときの、表示する大きさと座標の変換のテスト
+use rust_webpack_template::engine::viewport::{Fit, Viewport};
+
+const WIDTH: i16 = 600;
+const HEIGHT: i16 = 600;
+
+fn viewport(window_width: f64, window_height: f64, device_pixel_ratio: f64) -> Viewport {
+    let mut viewport = Viewport::new(WIDTH, HEIGHT);
+    viewport.resize(window_width, window_height, device_pixel_ratio);
+    viewport
+}
+
+fn logical(viewport: &Viewport, x: f64, y: f64) -> Option<(i16, i16)> {
+    viewport.to_logical(x, y).map(|point| (point.x, point.y))
+}
+
+#[test]
+fn viewport_shows_the_game_as_is_until_resized() {
+    let viewport = Viewport::new(WIDTH, HEIGHT);
+
+    assert_eq!(viewport.fit(), Fit::Letterbox);
+    assert_eq!(viewport.display_size(), (600., 600.));
+    assert_eq!(viewport.backing_size(), (600, 600));
+    assert_eq!(viewport.scale(), (1., 1.));
+    assert_eq!(logical(&viewport, 123., 456.), Some((123, 456)));
+}
+
+#[test]
+fn letterbox_keeps_the_aspect_ratio() {
+    // 横長のウィンドウでは高さに、縦長のウィンドウでは幅に合わせる
+    let wide = viewport(1920., 1080., 1.);
+    assert_eq!(wide.display_size(), (1080., 1080.));
+    assert_eq!(wide.backing_size(), (1080, 1080));
+    assert_eq!(wide.scale(), (1.8, 1.8));
+
+    let tall = viewport(800., 1200., 1.);
+    assert_eq!(tall.display_size(), (800., 800.));
+}
+
+#[test]
+fn high_dpi_screens_get_more_canvas_pixels_at_the_same_display_size() {
+    let viewport = viewport(1920., 1080., 2.);
+
+    assert_eq!(viewport.display_size(), (1080., 1080.));
+    assert_eq!(viewport.backing_size(), (2160, 2160));
+    assert_eq!(viewport.scale(), (3.6, 3.6));
+}
+
+#[test]
+fn fractional_device_pixel_ratio_still_fills_the_whole_canvas() {
+    let viewport = viewport(1000., 700., 1.25);
+
+    assert_eq!(viewport.backing_size(), (875, 875));
+    // ゲームの画面の右下の端が、canvas の右下の端にちょうど重なる
+    let (scale_x, scale_y) = viewport.scale();
+    assert_eq!(WIDTH as f64 * scale_x, 875.);
+    assert_eq!(HEIGHT as f64 * scale_y, 875.);
+}
+
+#[test]
+fn stretch_fills_the_window() {
+    let mut viewport = Viewport::new(WIDTH, HEIGHT).with_fit(Fit::Stretch);
+    viewport.resize(1920., 1080., 1.);
+
+    assert_eq!(viewport.display_size(), (1920., 1080.));
+    assert_eq!(viewport.scale(), (3.2, 1.8));
+    assert_eq!(logical(&viewport, 960., 540.), Some((300, 300)));
+}
+
+#[test]
+fn canvas_positions_map_back_to_the_game_screen() {
+    for device_pixel_ratio in [1., 1.5, 2., 3.] {
+        let viewport = viewport(1920., 1080., device_pixel_ratio);
+
+        assert_eq!(logical(&viewport, 0., 0.), Some((0, 0)));
+        assert_eq!(logical(&viewport, 540., 540.), Some((300, 300)));
+        assert_eq!(logical(&viewport, 1079., 1079.), Some((599, 599)));
+        // canvas の外
+        assert_eq!(logical(&viewport, -1., 540.), None);
+        assert_eq!(logical(&viewport, 1080., 540.), None);
+    }
+}
+
+#[test]
+fn resize_reports_only_actual_changes() {
+    let mut viewport = Viewport::new(WIDTH, HEIGHT);
+
+    assert!(viewport.resize(1920., 1080., 1.));
+    assert!(!viewport.resize(1920., 1080., 1.));
+    // 解像度の違う画面にウィンドウを移したときは、大きさが同じでも変わる
+    assert!(viewport.resize(1920., 1080., 2.));
+    assert!(viewport.resize(1280., 720., 2.));
+}
+
+#[test]
+fn empty_windows_do_not_produce_an_empty_canvas() {
+    for (width, height, device_pixel_ratio) in
+        [(0., 0., 1.), (800., 600., 0.), (f64::NAN, 600., 1.)]
+    {
+        let viewport = viewport(width, height, device_pixel_ratio);
+        let (backing_width, backing_height) = viewport.backing_size();
+        let (scale_x, scale_y) = viewport.scale();
+
+        assert!(backing_width >= 1 && backing_height >= 1);
+        assert!(scale_x.is_finite() && scale_x > 0.);
+        assert!(scale_y.is_finite() && scale_y > 0.);
+    }
+}
@@ -4,7 +4,8 @@ const WasmPackPlugin = require("@wasm-tool/wasm-pack-plugin");
 
 const dist = path.resolve(__dirname, "dist");
 
-module.exports = {
+// --env.debug を付けたとき（npm start）だけ、デバッグ用のパネルとオーバーレイを含めてビルドする
+module.exports = (env = {}) => ({
   mode: "production",
   entry: {
     index: "./js/index.js"
@@ -23,6 +24,7 @@ module.exports = {
 
     new WasmPackPlugin({
       crateDirectory: __dirname,
+      extraArgs: env.debug ? "-- --features physics_debug,history_debug" : "",
     }),
   ]
-};
+});